@@ -0,0 +1,21 @@
+//! Renders a picture of a map to a PNG file without opening a window, see `headless::USAGE`.
+//! Needs no libSDL, so it is built without the `sdl` feature too.
+
+use std::process::exit;
+
+use hyperbolic_raycaster::headless::{self, Screenshot};
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let screenshot = match Screenshot::from_args(&args) {
+        Ok(screenshot) => screenshot,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, headless::USAGE);
+            exit(2);
+        }
+    };
+    if let Err(e) = screenshot.take() {
+        eprintln!("Error rendering {}: {}", screenshot.map.display(), e);
+        exit(1);
+    }
+}
@@ -0,0 +1,161 @@
+use serde::Deserialize;
+
+use crate::utils::color::RGBColor;
+
+/// The air between the player and the world: things fade into the fog color with the distance.
+/// The fog color also fills the directions where nothing is seen.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+pub struct Fog {
+    #[serde(flatten)]
+    pub model: FogModel,
+
+    #[serde(default)]
+    pub color: RGBColor,
+
+    /// The most of something at a finite distance the fog hides, so the rest always shows through.
+    /// Things infinitely far away are hidden all the same.
+    #[serde(default = "Fog::full")]
+    pub max_amount: f64,
+}
+
+/// How the amount of fog grows with the distance, written in the map file as e.g.
+/// `{"model": "exponential", "density": 0.4}`.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+#[serde(tag = "model", rename_all = "snake_case")]
+pub enum FogModel {
+    /// Grows evenly from none at `start` to full at `end`.
+    Linear { start: f64, end: f64 },
+
+    /// Each unit of distance lets through the same share of the light, like real fog.
+    Exponential { density: f64 },
+
+    /// Clear nearby, thickening quickly further away.
+    ExponentialSquared { density: f64 },
+
+    /// Clear up to `distance`, then fading the way things shrink in the hyperbolic plane:
+    /// with the circumference of a circle around the player, 2π sinh r, growing exponentially.
+    HyperbolicHorizon { distance: f64 },
+}
+
+impl Fog {
+    /// Fog hiding everything far enough away, the default `max_amount`.
+    fn full() -> f64 {
+        1.
+    }
+
+    /// How much of something at the given distance is hidden by the fog, 0 (nothing) to 1 (everything).
+    /// Things infinitely far away are always hidden.
+    pub fn amount(&self, distance: f64) -> f64 {
+        if !distance.is_finite() {
+            return 1.;
+        }
+        let amount = match self.model {
+            FogModel::Linear { start, end } => (distance - start) / (end - start),
+            FogModel::Exponential { density } => 1. - (-density * distance).exp(),
+            FogModel::ExponentialSquared { density } => 1. - (-(density * distance).powi(2)).exp(),
+            FogModel::HyperbolicHorizon { distance: clear } => {
+                1. - clear.sinh() / distance.max(clear).sinh()
+            }
+        };
+        amount.clamp(0., 1.).min(self.max_amount)
+    }
+}
+
+impl FogModel {
+    /// The next fog model, wrapping around. Each model starts with settings similar in reach to the others.
+    pub fn next(self) -> FogModel {
+        match self {
+            FogModel::Linear { .. } => FogModel::Exponential { density: 0.5 },
+            FogModel::Exponential { .. } => FogModel::ExponentialSquared { density: 0.35 },
+            FogModel::ExponentialSquared { .. } => FogModel::HyperbolicHorizon { distance: 1. },
+            FogModel::HyperbolicHorizon { .. } => FogModel::Linear { start: 0., end: 4. },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Black fog of the given model, hiding everything far enough away.
+    fn fog(model: FogModel) -> Fog {
+        Fog {
+            model,
+            color: RGBColor::black(),
+            max_amount: 1.,
+        }
+    }
+
+    fn assert_amount(fog: &Fog, distance: f64, expected: f64) {
+        let amount = fog.amount(distance);
+        assert!((amount - expected).abs() < 1e-9, "{:?} at {}: {} != {}", fog.model, distance, amount, expected);
+    }
+
+    #[test]
+    fn linear_fog_grows_evenly_between_start_and_end() {
+        let fog = fog(FogModel::Linear { start: 1., end: 3. });
+        assert_amount(&fog, 0., 0.);
+        assert_amount(&fog, 1., 0.);
+        assert_amount(&fog, 2., 0.5);
+        assert_amount(&fog, 3., 1.);
+        assert_amount(&fog, 10., 1.);
+    }
+
+    #[test]
+    fn exponential_fog_lets_through_the_same_share_per_unit() {
+        let fog = fog(FogModel::Exponential { density: 0.5 });
+        assert_amount(&fog, 0., 0.);
+        assert_amount(&fog, 2., 1. - (-1f64).exp());
+        assert_amount(&fog, 4., 1. - (-2f64).exp());
+    }
+
+    #[test]
+    fn exponential_squared_fog_thickens_with_the_square_of_the_distance() {
+        let fog = fog(FogModel::ExponentialSquared { density: 0.5 });
+        assert_amount(&fog, 0., 0.);
+        assert_amount(&fog, 2., 1. - (-1f64).exp());
+        assert_amount(&fog, 4., 1. - (-4f64).exp());
+    }
+
+    #[test]
+    fn hyperbolic_horizon_fades_as_one_over_sinh() {
+        let fog = fog(FogModel::HyperbolicHorizon { distance: 1. });
+        assert_amount(&fog, 0., 0.);
+        assert_amount(&fog, 1., 0.);
+        assert_amount(&fog, 3., 1. - 1f64.sinh() / 3f64.sinh());
+    }
+
+    #[test]
+    fn infinitely_far_things_are_hidden() {
+        for model in &[
+            FogModel::Linear { start: 0., end: 4. },
+            FogModel::Exponential { density: 0.5 },
+            FogModel::ExponentialSquared { density: 0.35 },
+            FogModel::HyperbolicHorizon { distance: 1. },
+        ] {
+            let fog = Fog {
+                max_amount: 0.75,
+                ..fog(*model)
+            };
+            assert_amount(&fog, f64::INFINITY, 1.);
+        }
+    }
+
+    #[test]
+    fn max_amount_lets_things_show_through() {
+        let fog = Fog {
+            max_amount: 0.75,
+            ..fog(FogModel::Linear { start: 0., end: 1. })
+        };
+        assert_amount(&fog, 0.5, 0.5);
+        assert_amount(&fog, 1., 0.75);
+        assert_amount(&fog, 100., 0.75);
+    }
+
+    #[test]
+    fn max_amount_defaults_to_full_fog() {
+        let fog: Fog = serde_json::from_str(r#"{"model": "exponential", "density": 0.5}"#).unwrap();
+        assert_eq!(fog.max_amount, 1.);
+        assert_eq!(fog.color, RGBColor::black());
+    }
+}
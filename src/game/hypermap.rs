@@ -1,19 +1,146 @@
-use crate::utils::{ hyperpoint::HyperWall, poincarepoint::PoincareWall};
+use std::{collections::HashMap, rc::Rc};
+
+use nalgebra::{Matrix3, Vector3};
+use serde::Deserialize;
+
+use crate::game::fog::Fog;
+use crate::game::light::{add_lights_at, Light, LightDefinition};
+use crate::game::sprite::{Sprite, SpriteDefinition};
+use crate::utils::{
+    color::Illumination,
+    hyperpoint::{HyperWall, Hyperpoint},
+    image::Image,
+    material::{Material, MaterialDefinition},
+    poincarepoint::{PoincareCircle, PoincarePoint, PoincareWall},
+};
+
+/// Number of points along each wall at which the lightmap is computed.
+const LIGHTMAP_SAMPLES: usize = 32;
+
+/// Layout of a map file.
+/// Only exists while a map is loaded, so the size of its variants doesn't matter.
+#[derive(Deserialize)]
+#[serde(untagged)]
+#[allow(clippy::large_enum_variant)]
+enum MapFile {
+    /// A bare array of walls.
+    Walls(Vec<PoincareWall>),
+
+    /// Walls along with the objects placed on the map.
+    Full {
+        walls: Vec<PoincareWall>,
+
+        /// Round walls, each split into two curved walls.
+        #[serde(default)]
+        circles: Vec<PoincareCircle>,
+
+        /// Named images used by the sprites and textures.
+        #[serde(default)]
+        images: HashMap<String, Image>,
+
+        /// Named materials used by the walls.
+        #[serde(default)]
+        materials: HashMap<String, MaterialDefinition>,
+
+        #[serde(default)]
+        sprites: Vec<SpriteDefinition>,
+
+        #[serde(default)]
+        lights: Vec<LightDefinition>,
+
+        /// The fog of the map, instead of the renderer's.
+        #[serde(default)]
+        fog: Option<Fog>,
+
+        /// Whether to precompute the light along the walls when loading,
+        /// instead of tracing shadows every frame.
+        #[serde(default)]
+        lightmap: bool,
+    },
+}
 
 /// Represents the map in the Minkowski hyperboloid model.
+#[derive(Clone)]
 pub struct HyperMap {
     /// Walls of the map.
     walls: Vec<HyperWall>,
+
+    /// Objects placed on the map.
+    sprites: Vec<Sprite>,
+
+    /// Light sources placed on the map.
+    lights: Vec<Light>,
+
+    /// The fog chosen by the map, if any.
+    fog: Option<Fog>,
+
+    /// All the rotations and translations done to the map since it was loaded, as one isometry
+    /// of the hyperboloid: it takes map coordinates as written in the file to the current ones.
+    transform: Matrix3<f64>,
 }
 
 impl HyperMap {
-    /// Creates a new map from the given JSON string.
+    /// Creates a new map from the given JSON string, which has to be a valid map, see `from_json`.
     ///
     /// # Parameters
-    ///    - `map_string`:	A JSON representation of the map, an array of PoincareWalls.
+    ///    - `map_string`:	A JSON representation of the map, an array of PoincareWalls or an object with `walls`, `circles`, `images`, `materials`, `sprites`, `lights` and `fog`.
     pub fn new(map_string: &str) -> HyperMap {
+        HyperMap::from_json(map_string).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    /// Creates a new map from the given JSON string, or tells what is wrong with it:
+    /// invalid JSON, names of materials or images not in the map, or walls too curved for their ends.
+    ///
+    /// # Parameters
+    ///    - `map_string`:  A JSON representation of the map, see `new`.
+    pub fn from_json(map_string: &str) -> Result<HyperMap, String> {
         // Parse JSON to PoincareWalls.
-        let walls: Vec<PoincareWall> = serde_json::from_str(map_string).unwrap();
+        let map_file = serde_json::from_str(map_string).map_err(|e| format!("Invalid map: {}", e))?;
+        let (mut walls, images, materials, sprites, lights, fog, lightmap) =
+            match map_file {
+                MapFile::Walls(walls) => (walls, HashMap::new(), HashMap::new(), Vec::new(), Vec::new(), None, false),
+                MapFile::Full {
+                    mut walls,
+                    circles,
+                    images,
+                    materials,
+                    sprites,
+                    lights,
+                    fog,
+                    lightmap,
+                } => {
+                    walls.extend(circles.into_iter().flat_map(|c| c.into_walls()));
+                    (walls, images, materials, sprites, lights, fog, lightmap)
+                }
+            };
+
+        // Sprites and textures share images by name.
+        let images: HashMap<String, Rc<Image>> = images
+            .into_iter()
+            .map(|(name, image)| (name, Rc::new(image)))
+            .collect();
+
+        // Walls share materials by name.
+        let materials: HashMap<String, Rc<Material>> = materials
+            .into_iter()
+            .map(|(name, material)| Ok((name, Rc::new(material.resolve(&images)?))))
+            .collect::<Result<_, String>>()?;
+        for wall in &mut walls {
+            if let Some(name) = &wall.material_name {
+                let material = materials
+                    .get(name)
+                    .ok_or_else(|| format!("Unknown wall material: {}", name))?
+                    .clone();
+                wall.color = material.front.base_color();
+                wall.reflective |= material.reflective;
+                wall.material = Some(material);
+            }
+        }
+
+        // Curved walls have to fit between their ends, rather than failing once they are drawn.
+        for wall in &walls {
+            wall.check_curvature()?;
+        }
 
         // Scrapped idea - representing the walls as a set sorted by distance to origin.
         // Would need to be checked and resorted every frame.
@@ -29,8 +156,69 @@ impl HyperMap {
 
         // Sort by distance to origin.
         transformed_walls.sort_unstable();
-        HyperMap {
+
+        let sprites = sprites
+            .into_iter()
+            .map(|s| {
+                Ok(Sprite {
+                    image: images
+                        .get(&s.image)
+                        .ok_or_else(|| format!("Unknown sprite image: {}", s.image))?
+                        .clone(),
+                    position: s.position.into(),
+                    size: s.size,
+                })
+            })
+            .collect::<Result<_, String>>()?;
+
+        let mut map = HyperMap {
             walls: transformed_walls,
+            sprites,
+            lights: lights.into_iter().map(|l| l.into()).collect(),
+            fog,
+            transform: Matrix3::identity(),
+        };
+        if lightmap {
+            map.bake_lightmaps();
+        }
+        Ok(map)
+    }
+
+    /// Precomputes the light of the map lights along every wall, shadows included.
+    pub fn bake_lightmaps(&mut self) {
+        let walls = self.get_walls_as_poincare();
+        let lights: Vec<(PoincarePoint, &Light)> = self
+            .lights
+            .iter()
+            .map(|light| (light.position.clone().into(), light))
+            .collect();
+
+        let lightmaps: Vec<Option<Rc<Vec<Illumination>>>> = self
+            .walls
+            .iter()
+            .zip(&walls)
+            .map(|(wall, poincare_wall)| {
+                // Infinitely long walls can't be sampled evenly, and curved walls aren't
+                // sampled along their curve, they are lit when rendering
+                if wall.beginning.is_ideal() || wall.end.is_ideal() || wall.through.is_some() {
+                    return None;
+                }
+
+                let samples = (0..LIGHTMAP_SAMPLES)
+                    .map(|i| {
+                        let t = i as f64 / (LIGHTMAP_SAMPLES - 1) as f64;
+                        let point: PoincarePoint = wall.beginning.interpolate(&wall.end, t).into();
+                        let mut illumination = Illumination::white(0.);
+                        add_lights_at(&mut illumination, &point, &lights, &walls, Some(poincare_wall));
+                        illumination
+                    })
+                    .collect();
+                Some(Rc::new(samples))
+            })
+            .collect();
+
+        for (wall, lightmap) in self.walls.iter_mut().zip(lightmaps) {
+            wall.lightmap = lightmap;
         }
     }
 
@@ -47,11 +235,72 @@ impl HyperMap {
         wallsp
     }
 
+    /// Returns iterator of Sprite references.
+    pub fn get_sprites_iter(&self) -> impl Iterator<Item = &Sprite> {
+        self.sprites.iter()
+    }
+
+    /// Returns iterator of Light references.
+    pub fn get_lights_iter(&self) -> impl Iterator<Item = &Light> {
+        self.lights.iter()
+    }
+
+    /// The fog chosen by the map, if any.
+    pub fn get_fog(&self) -> Option<&Fog> {
+        self.fog.as_ref()
+    }
+
+    /// Hyperbolic distance of the player from the origin of the map, as written in the file.
+    pub fn player_distance_from_origin(&self) -> f64 {
+        // The player stays at the origin, the map's origin has moved
+        let origin = self.transform * Vector3::new(0., 0., 1.);
+        origin[2].max(1.).acosh()
+    }
+
+    /// The direction the player looks in, as an angle from the map's X axis towards its Y axis (-π…π).
+    /// The map's axes are carried from its origin to the player along the geodesic between them.
+    pub fn player_heading(&self) -> f64 {
+        // The inverse of an isometry of the hyperboloid is its Minkowski adjoint J * T^t * J
+        let j = Matrix3::from_diagonal(&Vector3::new(1., 1., -1.));
+        let inverse = j * self.transform.transpose() * j;
+
+        // The player's position and view direction in map coordinates
+        let position = inverse * Vector3::new(0., 0., 1.);
+        let direction = inverse * Vector3::new(1., 0., 0.);
+
+        // Carry the direction back to the map's origin, by the translation moving the player there
+        let (x, y, z) = (-position[0], -position[1], position[2]);
+        let offset_squared = x.powi(2) + y.powi(2);
+        let back = match offset_squared > 1e-18 {
+            true => {
+                let k = (z - 1.) / offset_squared;
+                Matrix3::new(
+                    1. + k * x * x, k * x * y, x,
+                    k * x * y, 1. + k * y * y, y,
+                    x, y, z,
+                )
+            }
+            false => Matrix3::identity(),
+        };
+        let direction = back * direction;
+        direction[1].atan2(direction[0])
+    }
+
     /// Rotate all walls around an origin.
     pub fn rotate(&mut self, step: f64) {
+        self.transform = Hyperpoint::rotation_matrix(step) * self.transform;
         for wall in &mut self.walls {
             wall.beginning.rotate(step);
             wall.end.rotate(step);
+            if let Some(through) = &mut wall.through {
+                through.rotate(step);
+            }
+        }
+        for sprite in &mut self.sprites {
+            sprite.position.rotate(step);
+        }
+        for light in &mut self.lights {
+            light.position.rotate(step);
         }
         // Keep walls sorted
         self.walls.sort_unstable();
@@ -59,11 +308,103 @@ impl HyperMap {
 
     /// Move all walls along the x and y axes.
     pub fn translate(&mut self, x: f64, y: f64) {
+        self.transform = Hyperpoint::translation_matrix(x, y) * self.transform;
         for wall in &mut self.walls {
             wall.beginning.translate(x, y);
             wall.end.translate(x, y);
+            if let Some(through) = &mut wall.through {
+                through.translate(x, y);
+            }
+        }
+        for sprite in &mut self.sprites {
+            sprite.position.translate(x, y);
+        }
+        for light in &mut self.lights {
+            light.position.translate(x, y);
         }
         // Keep walls sorted
         self.walls.sort_unstable();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The error of loading a map, which has to be invalid.
+    fn error(map: &str) -> String {
+        match HyperMap::from_json(map) {
+            Ok(_) => panic!("{} is loaded", map),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn maps_are_loaded() {
+        for map in &[
+            include_str!("../../assets/5square.json"),
+            include_str!("../../assets/curves.json"),
+            include_str!("../../assets/materials.json"),
+            include_str!("../../assets/sprites.json"),
+        ] {
+            assert!(HyperMap::from_json(map).is_ok());
+        }
+    }
+
+    #[test]
+    fn invalid_maps_are_reported() {
+        assert!(error(r#"{"walls": ["#).starts_with("Invalid map"));
+        assert_eq!(
+            error(r#"[{"beginning": [0, 0], "end": [0.4, 0], "material": "stone"}]"#),
+            "Unknown wall material: stone"
+        );
+        assert_eq!(
+            error(r#"{"walls": [], "sprites": [{"image": "tree", "position": [0.1, 0], "size": 0.05}]}"#),
+            "Unknown sprite image: tree"
+        );
+        assert_eq!(
+            error(r#"[{"beginning": [0, 0], "end": [0.4, 0], "curvature": 3}]"#),
+            "The wall from [0, 0] to [0.4, 0] cannot have the curvature 3"
+        );
+    }
+
+    /// Whether two angles are the same direction.
+    fn same_angle(a: f64, b: f64) -> bool {
+        let full_turn = 2. * std::f64::consts::PI;
+        !(1e-9..=full_turn - 1e-9).contains(&(a - b).rem_euclid(full_turn))
+    }
+
+    /// Turning the map by an angle turns the player the other way.
+    #[test]
+    fn heading_turns_against_the_map() {
+        for angle in [0.3, 2., -1.2, 4.] {
+            let mut map = HyperMap::new("[]");
+            map.rotate(angle);
+            assert!(same_angle(map.player_heading(), -angle), "{} after turning by {}", map.player_heading(), angle);
+        }
+    }
+
+    #[test]
+    fn distance_is_the_length_moved_from_the_origin() {
+        let mut map = HyperMap::new("[]");
+        map.translate(0.8, 0.);
+        assert!((map.player_distance_from_origin() - 0.8).abs() < 1e-9);
+
+        let mut map = HyperMap::new("[]");
+        map.rotate(1.1);
+        map.translate(0., -1.5);
+        assert!((map.player_distance_from_origin() - 1.5).abs() < 1e-9);
+    }
+
+    /// Moving forward goes along a geodesic, without turning the player and adding up the distance.
+    #[test]
+    fn moving_forward_keeps_the_heading() {
+        let mut map = HyperMap::new("[]");
+        map.rotate(0.7);
+        for step in 1..=4 {
+            map.translate(0.5, 0.);
+            assert!(same_angle(map.player_heading(), -0.7), "{} after {} steps", map.player_heading(), step);
+            assert!((map.player_distance_from_origin() - 0.5 * step as f64).abs() < 1e-9);
+        }
+    }
+}
@@ -0,0 +1,160 @@
+use serde::Deserialize;
+
+use crate::utils::{
+    color::{Illumination, RGBColor},
+    hyperpoint::Hyperpoint,
+    point::Point,
+    poincarepoint::{PoincarePoint, PoincareWall},
+};
+
+/// Below this distance a light is treated as if it was this far,
+/// so surfaces right next to it do not get infinitely bright.
+const NEAR_DISTANCE: f64 = 0.05;
+
+/// A point light source placed on the map.
+#[derive(Clone, Debug)]
+pub struct Light {
+    /// Position of the light.
+    pub position: Hyperpoint,
+
+    /// Color of the light.
+    pub color: RGBColor,
+
+    /// Brightness of the light at `NEAR_DISTANCE` from it.
+    pub intensity: f64,
+
+    /// Distance after which the light does not reach.
+    pub radius: f64,
+}
+
+impl Light {
+    /// Brightness of the light at the given distance from it.
+    ///
+    /// In the hyperbolic plane the light emitted by a point spreads along circles
+    /// of circumference 2π sinh r, instead of 2π r, so it falls off as 1 / sinh r:
+    /// close by almost like in the Euclidean plane, and exponentially further away.
+    pub fn brightness_at(&self, distance: f64) -> f64 {
+        if distance >= self.radius {
+            return 0.;
+        }
+        let spread = NEAR_DISTANCE.sinh() / distance.max(NEAR_DISTANCE).sinh();
+
+        // Fade out towards the edge of the radius instead of cutting off sharply
+        let fade = 1. - (distance / self.radius).powi(2);
+        self.intensity * spread * fade
+    }
+}
+
+/// Adds up the light of all lights reaching a point.
+/// A light does not reach the point if the geodesic segment between them is
+/// blocked by a wall.
+///
+/// # Parameters:
+///     - illumination:     The light to add to.
+///     - point:            The lit point.
+///     - lights:           The lights, with their positions in the Poincare disk.
+///     - walls:            The walls that may cast shadows.
+///     - lit_wall:         The wall the point lies on, if any. It does not shadow itself.
+pub fn add_lights_at(
+    illumination: &mut Illumination,
+    point: &PoincarePoint,
+    lights: &[(PoincarePoint, &Light)],
+    walls: &[PoincareWall],
+    lit_wall: Option<&PoincareWall>,
+) {
+    for (position, light) in lights {
+        let brightness = light.brightness_at(point.distance_to(position));
+        if brightness <= 0. {
+            continue;
+        }
+
+        let in_shadow = walls
+            .iter()
+            .filter(|wall| !lit_wall.is_some_and(|lit| std::ptr::eq(lit, *wall)))
+            .any(|wall| wall.blocks_segment(position, point));
+        if !in_shadow {
+            illumination.add_light(&light.color, brightness);
+        }
+    }
+}
+
+/// Looks up the light at the fraction `t` (0…1) of a wall's lightmap,
+/// interpolating between the nearest samples.
+pub fn sample_lightmap(lightmap: &[Illumination], t: f64) -> Illumination {
+    let position = t.clamp(0., 1.) * (lightmap.len() - 1) as f64;
+    let index = (position as usize).min(lightmap.len() - 2);
+    let fraction = position - index as f64;
+    let (a, b) = (&lightmap[index], &lightmap[index + 1]);
+    Illumination {
+        red: a.red + (b.red - a.red) * fraction,
+        green: a.green + (b.green - a.green) * fraction,
+        blue: a.blue + (b.blue - a.blue) * fraction,
+    }
+}
+
+/// Light as written in the map file.
+#[derive(Debug, Deserialize)]
+pub struct LightDefinition {
+    /// Position in the Poincare disk model.
+    pub position: PoincarePoint,
+    pub color: RGBColor,
+    pub intensity: f64,
+    pub radius: f64,
+}
+
+impl From<LightDefinition> for Light {
+    fn from(definition: LightDefinition) -> Light {
+        Light {
+            position: definition.position.into(),
+            color: definition.color,
+            intensity: definition.intensity,
+            radius: definition.radius,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A white light reaching `radius`.
+    fn light(intensity: f64, radius: f64) -> Light {
+        Light {
+            position: Hyperpoint::new(0., 0.),
+            color: RGBColor::white(),
+            intensity,
+            radius,
+        }
+    }
+
+    #[test]
+    fn light_is_capped_near_its_position() {
+        let light = light(2., 1000.);
+        assert!((light.brightness_at(0.) - 2.).abs() < 1e-6);
+        assert!((light.brightness_at(NEAR_DISTANCE / 2.) - 2.).abs() < 1e-6);
+        assert!((light.brightness_at(NEAR_DISTANCE) - 2.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn light_falls_off_as_one_over_sinh() {
+        // A radius so large the fade towards it does not count
+        let light = light(1., 1e6);
+        for &(near, far) in &[(0.1, 0.2), (0.5, 1.5), (2., 5.)] {
+            let ratio = light.brightness_at(far) / light.brightness_at(near);
+            assert!((ratio - near.sinh() / far.sinh()).abs() < 1e-6, "{} to {}: {}", near, far, ratio);
+        }
+
+        // Exponentially far away, unlike 1 / r
+        let ratio = light.brightness_at(11.) / light.brightness_at(10.);
+        assert!((ratio - (-1f64).exp()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn light_fades_out_at_its_radius() {
+        let light = light(1., 2.);
+        assert!(light.brightness_at(1.9) > 0.);
+        assert!(light.brightness_at(1.9) < light.brightness_at(1.8));
+        assert_eq!(light.brightness_at(2.), 0.);
+        assert_eq!(light.brightness_at(3.), 0.);
+    }
+}
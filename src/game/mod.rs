@@ -1,17 +1,28 @@
+pub mod fog;
 pub mod hypermap;
+pub mod light;
+pub mod player;
+pub mod sprite;
 
 use crate::game::hypermap::*;
+use crate::game::player::Player;
 
 /// Represents the state of our game's virtual world
 pub struct Game {
     /// The map of our virtual world
     pub map: HyperMap,
+
+    /// The player's view pitch and eye height
+    pub player: Player,
 }
 
 impl Game {
     /// Initializes a new game based on a given map and player.
     pub fn new(map: HyperMap) -> Game {
-        Game { map }
+        Game {
+            map,
+            player: Player::new(),
+        }
     }
 
     /// Rotates the player's viewing angle with the given angle.
@@ -0,0 +1,201 @@
+use std::time::Duration;
+
+/// The vertical state of the player: where it looks up or down, and how high its eyes are.
+/// Moving on the plane is done by moving the map around the player, see `HyperMap`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Player {
+    /// Angle of the view above the horizon (negative: below).
+    pitch: f64,
+
+    /// Height of the feet above the floor, while jumping.
+    elevation: f64,
+
+    /// Vertical speed, per second.
+    vertical_speed: f64,
+
+    /// How far the player has crouched down, 0 (standing) to 1 (crouching).
+    crouch: f64,
+
+    /// Whether the player wants to crouch.
+    crouching: bool,
+}
+
+impl Player {
+    /// The furthest the player can look up or down, in radians.
+    pub const MAX_PITCH: f64 = 0.6;
+
+    /// Acceleration pulling the player down to the floor, per second squared.
+    pub const GRAVITY: f64 = 0.9;
+
+    /// Upward speed at the start of a jump, per second. Jumps reach about 0.04, less than half a wall.
+    pub const JUMP_SPEED: f64 = 0.27;
+
+    /// How much of the eye height is lost when crouching.
+    pub const CROUCH_DEPTH: f64 = 0.5;
+
+    /// How long it takes to crouch down or stand up, in seconds.
+    pub const CROUCH_TIME: f64 = 0.15;
+
+    /// A player standing on the floor, looking at the horizon.
+    pub fn new() -> Player {
+        Player {
+            pitch: 0.,
+            elevation: 0.,
+            vertical_speed: 0.,
+            crouch: 0.,
+            crouching: false,
+        }
+    }
+
+    /// Angle of the view above the horizon (negative: below).
+    pub fn pitch(&self) -> f64 {
+        self.pitch
+    }
+
+    /// Tilts the view up by the given angle (down if negative), up to `MAX_PITCH`.
+    pub fn look_up(&mut self, angle: f64) {
+        self.pitch = (self.pitch + angle).clamp(-Self::MAX_PITCH, Self::MAX_PITCH);
+    }
+
+    /// Starts a jump, if the player is standing on the floor.
+    pub fn jump(&mut self) {
+        if self.elevation <= 0. {
+            self.vertical_speed = Self::JUMP_SPEED;
+        }
+    }
+
+    /// Sets whether the player crouches down or stands up.
+    pub fn set_crouching(&mut self, crouching: bool) {
+        self.crouching = crouching;
+    }
+
+    /// Moves the player vertically by the time passed since the last update.
+    pub fn update(&mut self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+
+        if self.elevation > 0. || self.vertical_speed > 0. {
+            self.elevation += self.vertical_speed * seconds - Self::GRAVITY * seconds.powi(2) / 2.;
+            self.vertical_speed -= Self::GRAVITY * seconds;
+            if self.elevation <= 0. {
+                self.elevation = 0.;
+                self.vertical_speed = 0.;
+            }
+        }
+
+        let target = if self.crouching { 1. } else { 0. };
+        let step = seconds / Self::CROUCH_TIME;
+        self.crouch = (self.crouch + (target - self.crouch).clamp(-step, step)).clamp(0., 1.);
+    }
+
+    /// Whether the player is still moving up or down, so the view keeps changing.
+    pub fn is_moving(&self) -> bool {
+        let target = if self.crouching { 1. } else { 0. };
+        self.elevation > 0. || self.vertical_speed > 0. || self.crouch != target
+    }
+
+    /// Height of the player's eyes above the floor.
+    ///
+    /// # Parameters:
+    ///     - standing_height:  The eye height of the player standing on the floor.
+    pub fn eye_height(&self, standing_height: f64) -> f64 {
+        standing_height * (1. - Self::CROUCH_DEPTH * self.crouch) + self.elevation
+    }
+}
+
+impl Default for Player {
+    fn default() -> Self {
+        Player::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Jumps, crouches and stands up again, updating by the given steps in turn.
+    fn play(steps: &[Duration]) -> Vec<Player> {
+        let mut player = Player::new();
+        let mut states = vec![];
+        for (frame, step) in steps.iter().cycle().take(200).enumerate() {
+            match frame {
+                0 => player.jump(),
+                20 => player.set_crouching(true),
+                120 => player.set_crouching(false),
+                _ => {}
+            }
+            player.update(*step);
+            states.push(player.clone());
+        }
+        states
+    }
+
+    /// Updates the player by the given number of 10 ms steps.
+    fn update_steps(player: &mut Player, steps: usize) {
+        for _ in 0..steps {
+            player.update(Duration::from_millis(10));
+        }
+    }
+
+    /// A jump reaches JUMP_SPEED² / (2 GRAVITY) above the floor after JUMP_SPEED / GRAVITY = 0.3 s.
+    #[test]
+    fn jump_reaches_its_apex() {
+        let mut player = Player::new();
+        player.jump();
+        update_steps(&mut player, 30);
+
+        let apex = Player::JUMP_SPEED.powi(2) / (2. * Player::GRAVITY);
+        assert!((player.eye_height(1.) - (1. + apex)).abs() < 1e-9, "{}", player.eye_height(1.));
+        assert!(player.vertical_speed.abs() < 1e-9);
+    }
+
+    /// A jump takes twice as long to come down as to go up, landing after 0.6 s.
+    #[test]
+    fn jump_lands_after_twice_the_time_to_its_apex() {
+        let mut player = Player::new();
+        player.jump();
+        update_steps(&mut player, 59);
+        assert!(player.eye_height(1.) > 1.);
+        assert!(player.is_moving());
+
+        update_steps(&mut player, 2);
+        assert_eq!(player.eye_height(1.), 1.);
+        assert!(!player.is_moving());
+    }
+
+    /// Crouching lowers the eyes by CROUCH_DEPTH of their height, halfway after half of CROUCH_TIME.
+    #[test]
+    fn crouching_takes_crouch_time() {
+        let mut player = Player::new();
+        player.set_crouching(true);
+        update_steps(&mut player, 15);
+        assert!((player.eye_height(2.) - 2. * (1. - Player::CROUCH_DEPTH)).abs() < 1e-9);
+
+        player.set_crouching(false);
+        update_steps(&mut player, 7);
+        player.update(Duration::from_micros(5_000));
+        assert!((player.eye_height(2.) - 2. * (1. - Player::CROUCH_DEPTH / 2.)).abs() < 1e-9);
+    }
+
+    /// The view stops at MAX_PITCH above or below the horizon.
+    #[test]
+    fn pitch_is_clamped() {
+        let mut player = Player::new();
+        player.look_up(1.);
+        assert_eq!(player.pitch(), 0.6);
+        player.look_up(-0.2);
+        assert!((player.pitch() - 0.4).abs() < 1e-12);
+        player.look_up(-2.);
+        assert_eq!(player.pitch(), -0.6);
+    }
+
+    #[test]
+    fn jump_lands_back_on_the_floor() {
+        let states = play(&[Duration::from_millis(20)]);
+        assert!(states[5].eye_height(1.) > 1.);
+        assert!(states.iter().all(|player| player.elevation >= 0.));
+
+        let last = states.last().unwrap();
+        assert_eq!(last.eye_height(1.), 1.);
+        assert!(!last.is_moving());
+    }
+}
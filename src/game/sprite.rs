@@ -0,0 +1,32 @@
+use std::rc::Rc;
+
+use serde::Deserialize;
+
+use crate::utils::{hyperpoint::Hyperpoint, image::Image, poincarepoint::PoincarePoint};
+
+/// An object standing in the world, drawn as a billboard
+/// always facing the player (markers, pickups, NPCs...).
+#[derive(Clone, Debug)]
+pub struct Sprite {
+    /// Position of the sprite's foot point.
+    pub position: Hyperpoint,
+
+    /// The picture of the sprite.
+    pub image: Rc<Image>,
+
+    /// Height of the sprite, in the same units as the walls (a wall is 0.1 high).
+    pub size: f64,
+}
+
+/// Sprite as written in the map file.
+#[derive(Debug, Deserialize)]
+pub struct SpriteDefinition {
+    /// Position in the Poincare disk model.
+    pub position: PoincarePoint,
+
+    /// Name of the image in the map's image library.
+    pub image: String,
+
+    /// Height of the sprite.
+    pub size: f64,
+}
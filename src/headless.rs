@@ -0,0 +1,266 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::game::hypermap::HyperMap;
+use crate::game::Game;
+use crate::layout::Layout;
+use crate::renderer::{RendererKind, Renderers};
+use crate::utils::color::RGBColor;
+use crate::window::canvas::Canvas;
+
+/// How to use the `render` subcommand of the binary, or the `hyperbolic-raycaster-render` binary built without SDL.
+pub const USAGE: &str = "Usage: hyperbolic-raycaster render <map.json> <output.png> [options]
+       hyperbolic-raycaster-render <map.json> <output.png> [options]
+
+Options:
+    --size <width>x<height>   Resolution of the picture (default 800x600)
+    --forward <distance>      Move the player forward from the start of the map
+    --strafe <distance>       Then move the player to the right
+    --turn <angle>            Then turn the player left, in radians
+    --pitch <angle>           Look up, in radians
+    --top-down                Render the top-down view of the Poincaré disk
+    --layout <layout>         Divide the picture into views: single (default), side-by-side
+                              (first-person and top-down) or models (four top-down projections)
+    --debug-rays <n>          Draw every n-th ray cast on the top-down view of the side-by-side
+                              layout and on the minimap";
+
+/// An RGB picture in memory, rendered without a window.
+pub struct Frame {
+    width: usize,
+    height: usize,
+
+    /// Red, green and blue bytes of every pixel, row by row from the top left.
+    pixels: Vec<u8>,
+}
+
+impl Frame {
+    /// Creates a black picture of the given size.
+    pub fn new(width: usize, height: usize) -> Frame {
+        Frame {
+            width,
+            height,
+            pixels: vec![0; width * height * 3],
+        }
+    }
+
+    /// The width of the picture
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The height of the picture
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Red, green and blue bytes of every pixel, row by row from the top left.
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// The color of the pixel at the given coordinates.
+    pub fn pixel(&self, x: usize, y: usize) -> RGBColor {
+        let offset = (y * self.width + x) * 3;
+        RGBColor {
+            red: self.pixels[offset],
+            green: self.pixels[offset + 1],
+            blue: self.pixels[offset + 2],
+        }
+    }
+
+    /// Copies the pixels of a canvas into the picture, starting at the top left corner.
+    /// Parts of the canvas outside of the picture are left out, parts of the picture outside
+    /// of the canvas are left as they are.
+    pub fn copy_from(&mut self, canvas: &Canvas) {
+        let width = self.width.min(canvas.width());
+        for y in 0..self.height.min(canvas.height()) {
+            let start = y * self.width * 3;
+            self.pixels[start..start + width * 3].copy_from_slice(&canvas.row(y)[..width * 3]);
+        }
+    }
+
+    /// A canvas drawing into the picture.
+    pub fn canvas(&mut self) -> Canvas<'_> {
+        Canvas::new(&mut self.pixels, self.width * 3, self.width, self.height)
+    }
+
+    /// Writes the picture in the PNG format.
+    pub fn write_png<W: Write>(&self, writer: W) -> io::Result<()> {
+        let mut encoder = png::Encoder::new(writer, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+        let mut writer = encoder.write_header()?;
+        writer.write_image_data(&self.pixels)?;
+        Ok(())
+    }
+
+    /// Saves the picture as a PNG file.
+    pub fn save_png<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        self.write_png(BufWriter::new(File::create(path)?))
+    }
+}
+
+/// Where the player stands and looks, relative to the start of the map.
+/// The player first moves, then strafes, then turns.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Pose {
+    /// Distance moved forward.
+    pub forward: f64,
+
+    /// Distance moved to the right.
+    pub strafe: f64,
+
+    /// Angle turned to the left, in radians.
+    pub turn: f64,
+
+    /// Angle looked up, in radians.
+    pub pitch: f64,
+}
+
+impl Pose {
+    /// Puts the player of a game in this pose.
+    pub fn apply(&self, game: &mut Game) {
+        game.move_player(-self.forward);
+        game.strafe_player(self.strafe);
+        game.rotate_player(self.turn);
+        game.player.look_up(self.pitch);
+    }
+}
+
+/// Renders a map seen from a pose into a picture, without a window.
+/// The renderers are set up as in the window.
+///
+/// # Parameters:
+///     - map:                The map to render.
+///     - pose:               Where the player stands and looks.
+///     - renderer:           The renderer to draw with, in the single layout.
+///     - layout:             How the picture is divided into views.
+///     - debug_ray_step:     Draw every how many'th ray cast on the top-down view, 0 for none.
+///     - width:              Width of the picture, in pixels.
+///     - height:             Height of the picture, in pixels.
+pub fn render(
+    map: HyperMap,
+    pose: &Pose,
+    renderer: RendererKind,
+    layout: Layout,
+    debug_ray_step: usize,
+    width: usize,
+    height: usize,
+) -> Frame {
+    let mut game = Game::new(map);
+    pose.apply(&mut game);
+
+    let mut renderers = Renderers::new(&game, 1.0, 0.75, 1., 0.25);
+    renderers.kind = renderer;
+    renderers.layout = layout;
+    renderers.hyperbolic.debug_ray_step = debug_ray_step;
+
+    let mut frame = Frame::new(width, height);
+    renderers.render(&game, &mut frame.canvas());
+    frame
+}
+
+/// A picture to render from the command line, see `USAGE`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Screenshot {
+    /// The map file.
+    pub map: PathBuf,
+
+    /// The PNG file to write.
+    pub output: PathBuf,
+
+    pub pose: Pose,
+    pub renderer: RendererKind,
+    pub layout: Layout,
+
+    /// Draw every how many'th ray cast on the top-down view, 0 for none.
+    pub debug_ray_step: usize,
+
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Screenshot {
+    /// Reads the arguments following the `render` subcommand, or of the `hyperbolic-raycaster-render` binary.
+    /// Returns a message telling what is wrong with them if they are not valid.
+    pub fn from_args(args: &[String]) -> Result<Screenshot, String> {
+        let mut paths = Vec::new();
+        let mut screenshot = Screenshot {
+            map: PathBuf::new(),
+            output: PathBuf::new(),
+            pose: Pose::default(),
+            renderer: RendererKind::Hyperbolic,
+            layout: Layout::Single,
+            debug_ray_step: 0,
+            width: 800,
+            height: 600,
+        };
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value of {}", arg));
+            match arg.as_str() {
+                "--size" => {
+                    let size = value()?;
+                    let (width, height) = size
+                        .split_once('x')
+                        .and_then(|(width, height)| Some((width.parse().ok()?, height.parse().ok()?)))
+                        .filter(|&(width, height)| width > 0 && height > 0)
+                        .ok_or(format!("Invalid size: {}", size))?;
+                    screenshot.width = width;
+                    screenshot.height = height;
+                }
+                "--forward" => screenshot.pose.forward = parse_number(arg, value()?)?,
+                "--strafe" => screenshot.pose.strafe = parse_number(arg, value()?)?,
+                "--turn" => screenshot.pose.turn = parse_number(arg, value()?)?,
+                "--pitch" => screenshot.pose.pitch = parse_number(arg, value()?)?,
+                "--top-down" => screenshot.renderer = RendererKind::Poincare,
+                "--layout" => {
+                    let name = value()?;
+                    screenshot.layout = Layout::from_name(name).ok_or(format!("Unknown layout: {}", name))?;
+                }
+                "--debug-rays" => {
+                    let step = value()?;
+                    screenshot.debug_ray_step = step
+                        .parse()
+                        .ok()
+                        .filter(|&step| step > 0)
+                        .ok_or(format!("Invalid value of {}: {}", arg, step))?;
+                }
+                option if option.starts_with("--") => return Err(format!("Unknown option: {}", option)),
+                path => paths.push(PathBuf::from(path)),
+            }
+        }
+
+        if paths.len() != 2 {
+            return Err("Expected a map file and an output file".to_string());
+        }
+        screenshot.output = paths.pop().unwrap();
+        screenshot.map = paths.pop().unwrap();
+        Ok(screenshot)
+    }
+
+    /// Renders the picture and saves it. A map file which is not a valid map is `io::ErrorKind::InvalidData`.
+    pub fn take(&self) -> io::Result<()> {
+        let map = std::fs::read_to_string(&self.map)?;
+        let map = HyperMap::from_json(&map).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        render(
+            map,
+            &self.pose,
+            self.renderer,
+            self.layout,
+            self.debug_ray_step,
+            self.width,
+            self.height,
+        )
+        .save_png(&self.output)
+    }
+}
+
+/// Reads the number given to an option.
+fn parse_number(option: &str, value: &str) -> Result<f64, String> {
+    value
+        .parse()
+        .map_err(|_| format!("Invalid value of {}: {}", option, value))
+}
@@ -0,0 +1,152 @@
+use std::time::{Duration, Instant};
+
+use crate::game::Game;
+use crate::utils::color::RGBColor;
+use crate::window::canvas::Canvas;
+
+/// Heads-up display: text in the top left corner of the screen telling how fast frames
+/// are rendered and where the player is.
+pub struct Hud {
+    /// Whether the HUD is drawn.
+    pub visible: bool,
+
+    /// Moving average of the time between shown frames, in seconds, while frames are shown one after another.
+    average_frame_interval: f64,
+
+    /// Moving average of the time it takes to render a frame, in seconds.
+    average_frame_time: f64,
+
+    /// When the last frame was shown.
+    last_frame: Option<Instant>,
+}
+
+impl Hud {
+    /// Weight of the newest frame in the moving averages.
+    const SMOOTHING: f64 = 0.1;
+
+    /// Longer times between frames are waits for input, which don't tell how fast frames are shown.
+    const MAX_FRAME_INTERVAL: Duration = Duration::from_millis(500);
+
+    /// A hidden HUD, with no frames measured yet.
+    pub fn new() -> Hud {
+        Hud {
+            visible: false,
+            average_frame_interval: 0.,
+            average_frame_time: 0.,
+            last_frame: None,
+        }
+    }
+
+    /// Measures a frame that has just been shown.
+    ///
+    /// # Parameters:
+    ///     - frame_time:   How long rendering the frame took.
+    pub fn record_frame(&mut self, frame_time: Duration) {
+        self.record_frame_shown_at(frame_time, Instant::now());
+    }
+
+    /// Measures a frame shown at the given time, see `record_frame`.
+    fn record_frame_shown_at(&mut self, frame_time: Duration, now: Instant) {
+        if let Some(last_frame) = self.last_frame {
+            let interval = now - last_frame;
+            if interval <= Self::MAX_FRAME_INTERVAL {
+                self.average_frame_interval = Self::smooth(self.average_frame_interval, interval.as_secs_f64());
+            }
+        }
+        self.average_frame_time = Self::smooth(self.average_frame_time, frame_time.as_secs_f64());
+        self.last_frame = Some(now);
+    }
+
+    /// Draws the HUD over a rendered frame, if it is visible.
+    ///
+    /// # Parameters:
+    ///     - game:         The world the frame shows.
+    ///     - rays_cast:    The number of rays cast for the frame.
+    ///     - canvas:       The canvas the frame was rendered to.
+    pub fn draw(&self, game: &Game, rays_cast: usize, canvas: &mut Canvas) {
+        if !self.visible {
+            return;
+        }
+
+        let heading = game.map.player_heading().to_degrees().rem_euclid(360.);
+        let lines = [
+            format!("FPS {:.0}", self.fps()),
+            format!("FRAME {:.1} MS", self.average_frame_time * 1000.),
+            format!("DISTANCE {:.2}", game.map.player_distance_from_origin()),
+            format!("HEADING {:.0}°", heading),
+            format!("WALLS {}", game.map.get_walls_iter().count()),
+            format!("RAYS {}", rays_cast),
+        ];
+
+        // Readable at any resolution, with a shadow to stand out from bright walls
+        let scale = (canvas.height() / 240).max(1);
+        let margin = 2 * scale;
+        let line_height = Canvas::text_height(scale) + 2 * scale;
+        for (index, line) in lines.iter().enumerate() {
+            let y = margin + index * line_height;
+            canvas.draw_text(margin + scale, y + scale, line, &RGBColor::black(), scale);
+            canvas.draw_text(margin, y, line, &RGBColor::white(), scale);
+        }
+    }
+
+    /// Frames shown per second while frames are shown one after another, e.g. while moving.
+    /// Before that, the frames per second rendering alone would allow.
+    fn fps(&self) -> f64 {
+        match (self.average_frame_interval, self.average_frame_time) {
+            (interval, _) if interval > 0. => 1. / interval,
+            (_, frame_time) if frame_time > 0. => 1. / frame_time,
+            _ => 0.,
+        }
+    }
+
+    /// Adds a new measurement to a moving average.
+    fn smooth(average: f64, value: f64) -> f64 {
+        match average == 0. {
+            true => value,
+            false => (1. - Self::SMOOTHING) * average + Self::SMOOTHING * value,
+        }
+    }
+}
+
+impl Default for Hud {
+    fn default() -> Self {
+        Hud::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fps_follows_the_time_between_frames() {
+        let mut hud = Hud::new();
+        let start = Instant::now();
+        for frame in 0..10 {
+            hud.record_frame_shown_at(Duration::from_millis(5), start + Duration::from_millis(20) * frame);
+        }
+        assert!((hud.fps() - 50.).abs() < 1e-6, "{}", hud.fps());
+    }
+
+    /// Waiting for input between frames doesn't make rendering look slow.
+    #[test]
+    fn waits_between_frames_are_left_out() {
+        let mut hud = Hud::new();
+        let start = Instant::now();
+        hud.record_frame_shown_at(Duration::from_millis(5), start);
+        hud.record_frame_shown_at(Duration::from_millis(5), start + Duration::from_millis(20));
+        hud.record_frame_shown_at(Duration::from_secs(5), start + Duration::from_secs(10));
+        assert!((hud.fps() - 50.).abs() < 1e-6, "{}", hud.fps());
+    }
+
+    #[test]
+    fn fps_of_the_render_time_before_frames_follow_each_other() {
+        let mut hud = Hud::new();
+        assert_eq!(hud.fps(), 0.);
+
+        let start = Instant::now();
+        hud.record_frame_shown_at(Duration::from_millis(10), start);
+        hud.record_frame_shown_at(Duration::from_millis(10), start + Duration::from_secs(3));
+        assert!((hud.fps() - 100.).abs() < 1e-6, "{}", hud.fps());
+    }
+}
@@ -1,129 +1,660 @@
-use crate::utils::color::RGBColor;
+use std::cell::{Cell, Ref, RefCell};
+
+use crate::utils::color::{Illumination, LinearColor, RGBColor};
+use crate::utils::material::Surface;
+use crate::utils::point::Point;
+use crate::utils::poincarepoint::PoincarePoint;
+use crate::poincare_renderer;
+use crate::poincare_renderer::viewport::Viewport;
+use crate::renderer;
 use crate::window::canvas::Canvas;
-use crate::{game::Game, utils::poincarepoint::PoincareWall};
+use crate::{
+    game::{
+        fog::{Fog, FogModel},
+        hypermap::HyperMap,
+        light::{add_lights_at, sample_lightmap, Light},
+        player::Player,
+        sprite::Sprite,
+        Game,
+    },
+    utils::poincarepoint::PoincareWall,
+};
+
+/// The parts of the world needed to render a frame,
+/// converted to the Poincare disk model once per frame.
+struct Scene<'a> {
+    walls: Vec<PoincareWall>,
+
+    /// The map lights, with their positions in the Poincare disk.
+    lights: Vec<(PoincarePoint, &'a Light)>,
+
+    /// The player looking at the scene, for the height and pitch of the view.
+    player: &'a Player,
+}
+
+impl<'a> Scene<'a> {
+    /// The scene as seen in a mirror: everything reflected across the mirror's geodesic.
+    fn reflected_in(&self, mirror: &PoincareWall) -> Scene<'a> {
+        Scene {
+            walls: self.walls.iter().map(|wall| mirror.reflect_wall(wall)).collect(),
+            lights: self
+                .lights
+                .iter()
+                .map(|(position, light)| (mirror.reflect_point(position), *light))
+                .collect(),
+            player: self.player,
+        }
+    }
+}
+
+/// The closest distance a wall is drawn at, so its height on the screen stays finite.
+const MINIMUM_WALL_DISTANCE: f64 = 1e-3;
+
+/// Height of the walls.
+/// 0.1 found by experiment. Works well with the scale of things on the Poincare disk coordinates
+const WALL_HEIGHT: f64 = 0.1; //todo:: Allow different wall heights
 
 enum Hit {
-    /// The ray hit a wall with a given color at a given distance.
-    Wall { color: RGBColor, distance: f64 },
+    /// The ray hit a wall looking a given way at a given distance.
+    Wall { shade: WallShade, distance: f64 },
 }
+
+/// The look of a wall where a ray hit it, sampled down the column.
+struct WallShade {
+    /// The side of the wall facing the ray.
+    surface: Surface,
+
+    /// Distance of the hit point along the wall, see `Surface::color_at`.
+    u: f64,
+
+    /// The light reaching the hit point.
+    illumination: Illumination,
+
+    /// How much of the wall the fog hides.
+    fog_amount: f64,
+
+    /// The colors of the mirrors the ray was reflected by, combined.
+    filter: LinearColor,
+}
+
+impl WallShade {
+    /// Color of the wall at the height `v`, 0 at the top and 1 at the bottom.
+    fn color_at(&self, v: f64, fog_color: &LinearColor) -> LinearColor {
+        LinearColor::from(&self.surface.color_at(self.u, v))
+            .illuminate(&self.illumination)
+            .mix(fog_color, self.fog_amount)
+            .tint(&self.filter)
+    }
+}
+/// A ray cast for a frame, as recorded for the ray debug view.
+#[derive(Clone, Debug)]
+pub struct DebugRay {
+    /// The direction of the ray, see `Renderer::ray_angle`.
+    pub angle: f64,
+
+    /// Where the ray met the first wall in its way, on the Poincare disk around the player,
+    /// and the wall's color. `None` if the ray hit nothing.
+    pub hit: Option<(PoincarePoint, RGBColor)>,
+}
+
 /// Raycaster in hyperbolic space.
 pub struct Renderer {
-    /// The state of the virtual world to be rendered
-    pub game: Game,
-
-    /// The radius around the player where objects should appear illuminated
-    pub illumination_radius: f64,
     pub relative_screen_size: f64,
     pub focal_length: f64,
 
-    /// The minimum environment light of the scene
-    pub minimum_light: f64,
-
+    /// Eye height of the player standing on the floor (a wall is 0.1 high)
     pub player_height: f64,
     pub field_of_vision: f64,
+
+    /// Rendering quality, trading speed for smooth output
+    pub quality: Quality,
+
+    /// How many times a ray may be reflected by mirrors
+    pub max_reflections: usize,
+
+    /// Whether to show the Poincare disk view in a corner of the screen
+    pub minimap: bool,
+
+    /// The zoomed, panned and recentered part of the disk shown by the minimap
+    pub viewport: Viewport,
+
+    /// How the directions around the player are laid out on the screen
+    pub view_mode: ViewMode,
+
+    /// Whether to render a view for each eye, for 3D viewing
+    pub stereo: Stereo,
+
+    /// Distance between the player's eyes, for stereo rendering
+    pub eye_separation: f64,
+
+    /// How things fade into the distance, and the color seen where there is nothing
+    pub fog: Fog,
+
+    /// Number of rays cast for the last frame, mirror reflections included, for one eye of stereo views
+    rays_cast: Cell<usize>,
+
+    /// Record every how many'th ray of a frame for the ray debug view, 0 to record none
+    pub debug_ray_step: usize,
+
+    /// The rays recorded for the last frame, see `debug_ray_step`
+    debug_rays: RefCell<Vec<DebugRay>>,
+
+    /// Whether rays are counted and recorded. Off for the second eye of stereo views,
+    /// so the numbers and the ray debug view are those of one view.
+    counting_rays: Cell<bool>,
+
+    /// The pictures of the left and the right eye for anaglyphs, kept between frames
+    anaglyph_buffers: RefCell<(Vec<u8>, Vec<u8>)>,
+}
+
+/// Ways of showing the views of both eyes for 3D viewing.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Stereo {
+    /// A single view from the player's position.
+    Off,
+
+    /// The left eye's view in the left half of the screen, the right eye's in the right half.
+    SideBySide,
+
+    /// Both views in the same frame, the left one in red and the right one in cyan, for red/cyan glasses.
+    Anaglyph,
+}
+
+impl Stereo {
+    /// The next stereo mode, wrapping around.
+    pub fn next(self) -> Stereo {
+        match self {
+            Stereo::Off => Stereo::SideBySide,
+            Stereo::SideBySide => Stereo::Anaglyph,
+            Stereo::Anaglyph => Stereo::Off,
+        }
+    }
+}
+
+/// Ways of laying out the directions around the player on the screen.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ViewMode {
+    /// A flat screen in front of the player, the regular first-person view.
+    Perspective,
+
+    /// The full 360° ring around the player, on a cylinder, with the view straight ahead in the middle.
+    Panorama,
+
+    /// The full sphere of directions, with longitude and latitude both linear on the screen.
+    Equirectangular,
+}
+
+impl ViewMode {
+    /// The next view mode, wrapping around.
+    pub fn next(self) -> ViewMode {
+        match self {
+            ViewMode::Perspective => ViewMode::Panorama,
+            ViewMode::Panorama => ViewMode::Equirectangular,
+            ViewMode::Equirectangular => ViewMode::Perspective,
+        }
+    }
+}
+
+/// Settings of the rendering quality.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Quality {
+    /// Number of rays cast for each column of the screen, averaged into its color.
+    pub samples_per_column: usize,
+
+    /// Whether rows covered partly by the end of a wall blend the wall with the ceiling or floor.
+    pub smooth_edges: bool,
+}
+
+impl Quality {
+    /// One ray per column, walls end on whole rows.
+    pub const LOW: Quality = Quality {
+        samples_per_column: 1,
+        smooth_edges: false,
+    };
+    pub const MEDIUM: Quality = Quality {
+        samples_per_column: 2,
+        smooth_edges: true,
+    };
+    pub const HIGH: Quality = Quality {
+        samples_per_column: 4,
+        smooth_edges: true,
+    };
+
+    /// The next quality setting, wrapping around from high to low.
+    pub fn next(self) -> Quality {
+        if self == Quality::LOW {
+            Quality::MEDIUM
+        } else if self == Quality::MEDIUM {
+            Quality::HIGH
+        } else {
+            Quality::LOW
+        }
+    }
+}
+
+/// Colors of the pixels of a single screen column, accumulated in linear light from several rays.
+struct ColumnBuffer {
+    pixels: Vec<LinearColor>,
+}
+
+impl ColumnBuffer {
+    fn new(height: usize) -> ColumnBuffer {
+        ColumnBuffer {
+            pixels: vec![LinearColor::black(); height],
+        }
+    }
+
+    fn height(&self) -> usize {
+        self.pixels.len()
+    }
+
+    fn clear(&mut self) {
+        self.pixels.iter_mut().for_each(|pixel| *pixel = LinearColor::black());
+    }
+
+    /// Adds the share `weight` of a color to a pixel.
+    fn add(&mut self, y: usize, color: &LinearColor, weight: f64) {
+        self.pixels[y] += *color * weight;
+    }
+
+    /// Draws the accumulated colors into a column of the canvas.
+    fn draw(&self, canvas: &mut Canvas, column: usize) {
+        for (y, pixel) in self.pixels.iter().enumerate() {
+            canvas.draw_linear_pixel(column, y, pixel);
+        }
+    }
 }
 
 impl Renderer {
-    /// Initializes the renderer with a map, a player and a focal length that should be used for rendering.
+    /// Initializes the renderer with a focal length that should be used for rendering.
     ///
     /// # Parameters:
-    /// 	- game:						The virtual world state (i.e. the game's map and player position)
+    /// 	- game:						The virtual world state, for the settings of its map (e.g. fog)
     ///		- relative_screen_size:		The size of the physical computer display in relation to a grid field
     ///  	- focal_length:				A focal length that should be used for rendering.
-    ///	 	- illumination_radius:		The radius around the player where objects should appear illuminated.
-    ///	 	- minimum_öight:			The minimum environment light of the scene.
+    ///     - illumination_radius:      The distance from which things are only lit by `minimum_light`, fading linearly up to it.
+    ///     - minimum_light:            The light left of things at a finite distance, however far. Unless the map has its own fog,
+    ///                                 this is a linear black fog, hiding only things infinitely far away completely.
     ///
     pub fn new(
-        game: Game,
+        game: &Game,
         relative_screen_size: f64,
         focal_length: f64,
         illumination_radius: f64,
         minimum_light: f64,
     ) -> Renderer {
+        let fog = game.map.get_fog().cloned().unwrap_or(Fog {
+            model: FogModel::Linear {
+                start: 0.,
+                end: illumination_radius,
+            },
+            color: RGBColor::black(),
+            max_amount: 1. - minimum_light,
+        });
+
         Renderer {
-            game,
             relative_screen_size,
             focal_length,
-            illumination_radius,
-            minimum_light,
             player_height: 0.05,
             field_of_vision: std::f64::consts::PI / 2.0,
+            quality: Quality::LOW,
+            max_reflections: 8,
+            minimap: false,
+            viewport: Viewport::default(),
+            view_mode: ViewMode::Perspective,
+            stereo: Stereo::Off,
+            eye_separation: 0.004,
+            fog,
+            rays_cast: Cell::new(0),
+            debug_ray_step: 0,
+            debug_rays: RefCell::new(Vec::new()),
+            counting_rays: Cell::new(true),
+            anaglyph_buffers: RefCell::new((Vec::new(), Vec::new())),
         }
     }
+}
 
-    /// Renders one frame into a canvas.
-    ///
-    /// # Parameters:
-    ///		- canvas		The canvas that should be drawn to.
-    pub fn render(&self, canvas: &mut Canvas) {
-        let walls: Vec<PoincareWall> = self.game.map.get_walls_as_poincare();
-        for column in 0..canvas.width() {
-            self.render_column(column, canvas, &walls);
-        }
-    }
-
-    fn render_column(&self, column: usize, canvas: &mut Canvas, walls: &[PoincareWall]) {
-        // Cast the ray to find a nearby wall
-        let scanning_result = self.cast_ray(column, canvas.width(), walls);
-
-        // Draw scanning result to the canvas
-        self.draw_hit(scanning_result, column, canvas);
-    }
-
-    fn cast_ray(&self, column: usize, max_column: usize, walls: &[PoincareWall]) -> Option<Hit> {
-        // Determine the absolute angle of the ray
-        let angle = self.ray_angle(column, max_column);
-        let mut closest_hit: Option<Hit> = None;
-
-        walls.iter().for_each(|wall| {
-            match wall.find_distance_of_intersection_with_ray(angle) {
-				// Ray hit wall
-                Some(distance) => {
-                    // Fix the calculated distance to correct the fisheye effect
-                    let projected_distance = distance * angle.cos();
-
-                    // Apply some lighting to the wall's color
-                    let distance_light_intensity = (1.0
-                        - projected_distance / self.illumination_radius)
-                        .max(self.minimum_light)
-                        .min(1.0);
-                    let illuminated_color = wall.color.adjust_light_intensity(
-                        distance_light_intensity
-                    );
-
-                    // Pass the result
-                    match &closest_hit {
-						// Ignore case when found hit is farther than closest hit up to this point
-						Some(Hit::Wall { color: _, distance }) if projected_distance >= *distance => (),
-						
-						// Update closest hit if it's None, or if we found a closer hit
-                        _ => {
-                            closest_hit = Some(Hit::Wall {
-                                color: illuminated_color,
-                                distance: projected_distance,
-                            })
-                        }
+impl renderer::Renderer for Renderer {
+    fn render(&self, game: &Game, canvas: &mut Canvas) {
+        self.rays_cast.set(0);
+        self.debug_rays.borrow_mut().clear();
+        match self.stereo {
+            Stereo::Off => self.render_view(&game.map, &game.player, canvas),
+
+            Stereo::SideBySide => {
+                let (left_eye, right_eye) = self.eye_maps(&game.map);
+                let width = canvas.width() / 2;
+                let height = canvas.height();
+                self.render_view(&left_eye, &game.player, &mut canvas.sub_canvas(0, 0, width, height));
+                self.render_other_eye(&right_eye, &game.player, &mut canvas.sub_canvas(width, 0, width, height));
+            }
+
+            Stereo::Anaglyph => {
+                let (left_eye, right_eye) = self.eye_maps(&game.map);
+                let (width, height) = (canvas.width(), canvas.height());
+                let mut buffers = self.anaglyph_buffers.borrow_mut();
+                let (left_buffer, right_buffer) = &mut *buffers;
+                left_buffer.resize(width * height * 3, 0);
+                right_buffer.resize(width * height * 3, 0);
+                self.render_view(&left_eye, &game.player, &mut Canvas::new(left_buffer, width * 3, width, height));
+                self.render_other_eye(&right_eye, &game.player, &mut Canvas::new(right_buffer, width * 3, width, height));
+
+                // Red for the left eye, green and blue (cyan) for the right eye
+                for y in 0..height {
+                    for x in 0..width {
+                        let offset = (y * width + x) * 3;
+                        let color = RGBColor {
+                            red: left_buffer[offset],
+                            green: right_buffer[offset + 1],
+                            blue: right_buffer[offset + 2],
+                        };
+                        canvas.draw_pixel(x, y, &color);
                     }
-				}
-				// Ray did not hit wall
-                None => (),
+                }
+            }
+        }
+
+        if self.minimap {
+            self.draw_minimap(game, canvas);
+        }
+    }
+
+    /// Number of rays cast for the last frame, mirror reflections included.
+    fn rays_cast(&self) -> usize {
+        self.rays_cast.get()
+    }
+
+    /// The view of the minimap.
+    fn viewport_mut(&mut self) -> Option<&mut Viewport> {
+        Some(&mut self.viewport)
+    }
+}
+
+impl Renderer {
+    /// Renders the view of the given map from the origin into a canvas.
+    fn render_view(&self, map: &HyperMap, player: &Player, canvas: &mut Canvas) {
+        let scene = Scene {
+            walls: map.get_walls_as_poincare(),
+            lights: map
+                .get_lights_iter()
+                .map(|light| (light.position.clone().into(), light))
+                .collect(),
+            player,
+        };
+
+        // Distance to the wall drawn in each column, used to hide sprites behind walls
+        let mut column_buffer = ColumnBuffer::new(canvas.height());
+        let depth_buffer: Vec<f64> = (0..canvas.width())
+            .map(|column| self.render_column(column, canvas, &scene, &mut column_buffer))
+            .collect();
+
+        self.draw_sprites(map, player, canvas, &depth_buffer);
+    }
+
+    /// Renders the view of the second eye of a stereo view, without counting or recording its rays.
+    fn render_other_eye(&self, map: &HyperMap, player: &Player, canvas: &mut Canvas) {
+        self.counting_rays.set(false);
+        self.render_view(map, player, canvas);
+        self.counting_rays.set(true);
+    }
+
+    /// The map as seen from the player's left and right eye. Each eye is half of
+    /// `eye_separation` away from the player, perpendicular to the view direction.
+    fn eye_maps(&self, map: &HyperMap) -> (HyperMap, HyperMap) {
+        // Moving an eye to the left moves the world to its right
+        let mut left_eye = map.clone();
+        left_eye.translate(0., -self.eye_separation / 2.);
+        let mut right_eye = map.clone();
+        right_eye.translate(0., self.eye_separation / 2.);
+        (left_eye, right_eye)
+    }
+
+    /// Draws the top-down Poincare disk view, with the player's view cone,
+    /// into the top right corner of the canvas.
+    fn draw_minimap(&self, game: &Game, canvas: &mut Canvas) {
+        let size = canvas.width().min(canvas.height()) / 3;
+        let margin = size / 20;
+        if size == 0 || size + margin > canvas.width() {
+            return;
+        }
+
+        let (left_angle, right_angle) = self.view_cone();
+        let mut minimap = canvas.sub_canvas(canvas.width() - size - margin, margin, size, size);
+        minimap.fill(&RGBColor::black());
+        poincare_renderer::Renderer::draw_map(game, &self.viewport, &mut minimap);
+        poincare_renderer::Renderer::draw_view_cone(left_angle, right_angle, &self.viewport, &mut minimap);
+        poincare_renderer::Renderer::draw_rays(&self.debug_rays(), &self.viewport, &mut minimap);
+    }
+
+    /// The rays recorded for the last frame, when `debug_ray_step` is set.
+    /// Mirrors end a recorded ray, what it meets past them is not recorded.
+    pub fn debug_rays(&self) -> Ref<'_, [DebugRay]> {
+        Ref::map(self.debug_rays.borrow(), Vec::as_slice)
+    }
+
+    /// The angles of the rays at the left and the right edge of the view, as drawn by
+    /// `poincare_renderer::Renderer::draw_view_cone`.
+    pub fn view_cone(&self) -> (f64, f64) {
+        (self.ray_angle(0., 1), self.ray_angle(1., 1))
+    }
+
+    /// Renders a single column and returns the distance of the nearest wall drawn in it.
+    /// With supersampling, several rays are cast across the width of the column
+    /// and their pictures are averaged.
+    fn render_column(
+        &self,
+        column: usize,
+        canvas: &mut Canvas,
+        scene: &Scene,
+        column_buffer: &mut ColumnBuffer,
+    ) -> f64 {
+        let samples = self.quality.samples_per_column.max(1);
+        let weight = 1. / samples as f64;
+        let mut depth = f64::INFINITY;
+
+        column_buffer.clear();
+        for sample in 0..samples {
+            // Spread the rays evenly over the column, each in the middle of its part of the column
+            let offset = (sample as f64 + 0.5) * weight;
+
+            // Determine the absolute angle of the ray
+            let angle = self.ray_angle(column as f64 + offset, canvas.width());
+
+            // Cast the ray to find a nearby wall, recording every `debug_ray_step`'th ray.
+            // Counting from 1, no ray is a multiple of 0.
+            let ray_number = column * samples + sample + 1;
+            let record = self.counting_rays.get() && ray_number.is_multiple_of(self.debug_ray_step);
+            let scanning_result = self.cast_ray(angle, scene, record);
+            if let Some(Hit::Wall { distance, .. }) = &scanning_result {
+                depth = depth.min(*distance);
+            }
+
+            // Draw scanning result to the column
+            self.draw_hit(scanning_result, angle, scene, column_buffer, weight);
+        }
+
+        column_buffer.draw(canvas, column);
+        depth
+    }
+
+    /// Casts a ray from the player, and records where it went for the ray debug view if asked to.
+    fn cast_ray(&self, angle: f64, scene: &Scene, record: bool) -> Option<Hit> {
+        let closest_hit = self.closest_hit(angle, scene, 0.);
+        if record {
+            self.debug_rays.borrow_mut().push(DebugRay {
+                angle,
+                hit: closest_hit
+                    .as_ref()
+                    .map(|(_, index, point)| (point.clone(), scene.walls[*index].color.clone())),
+            });
+        }
+        self.shade_hit(angle, scene, closest_hit, self.max_reflections)
+    }
+
+    /// Finds the closest wall hit by the ray further than `min_distance`, and shades it.
+    fn trace_ray(
+        &self,
+        angle: f64,
+        scene: &Scene,
+        min_distance: f64,
+        reflections_left: usize,
+    ) -> Option<Hit> {
+        let closest_hit = self.closest_hit(angle, scene, min_distance);
+        self.shade_hit(angle, scene, closest_hit, reflections_left)
+    }
+
+    /// Finds the closest wall hit by the ray further than `min_distance`.
+    /// Returns the distance of the hit, the index of the wall in the scene and the hit point.
+    fn closest_hit(
+        &self,
+        angle: f64,
+        scene: &Scene,
+        min_distance: f64,
+    ) -> Option<(f64, usize, PoincarePoint)> {
+        if self.counting_rays.get() {
+            self.rays_cast.set(self.rays_cast.get() + 1);
+        }
+        let mut closest_hit: Option<(f64, usize, PoincarePoint)> = None;
+
+        scene.walls.iter().enumerate().for_each(|(index, wall)| {
+            // Ray hit wall
+            if let Some((distance, point)) = wall.find_intersection_with_ray_beyond(angle, min_distance) {
+                match &closest_hit {
+                    // Ignore case when found hit is farther than closest hit up to this point
+                    Some((closest_distance, _, _)) if distance >= *closest_distance => (),
+
+                    // Update closest hit if it's None, or if we found a closer hit
+                    _ => closest_hit = Some((distance, index, point)),
+                }
             }
         });
         closest_hit
     }
 
-    fn draw_hit(&self, hit: Option<Hit>, column: usize, canvas: &mut Canvas) {
+    /// Works out the look of the wall a ray hit, see `closest_hit`.
+    /// A mirror continues the ray, reflected across the mirror's geodesic, as long as
+    /// `reflections_left` allows; the mirror's color tints what is seen in it.
+    fn shade_hit(
+        &self,
+        angle: f64,
+        scene: &Scene,
+        closest_hit: Option<(f64, usize, PoincarePoint)>,
+        reflections_left: usize,
+    ) -> Option<Hit> {
+        let (distance, index, point) = closest_hit?;
+        let wall = &scene.walls[index];
+
+        // Only geodesic walls are flat mirrors, reflecting the world through an isometry
+        if wall.reflective && wall.through.is_none() && reflections_left > 0 {
+            // Rather than bending the ray, reflect the world across the mirror. The ray goes on
+            // straight from the origin, and what it meets past the mirror is what the mirror shows.
+            // The mirror maps onto itself, so it is skipped by looking only past the hit.
+            let reflected_scene = scene.reflected_in(wall);
+            return self
+                .trace_ray(angle, &reflected_scene, distance + 1e-9, reflections_left - 1)
+                .map(|Hit::Wall { mut shade, distance }| {
+                    shade.filter = shade.filter.tint(&LinearColor::from(&wall.color));
+                    Hit::Wall { shade, distance }
+                });
+        }
+
+        // Far walls fade into the fog, walls reaching infinity disappear in it
+        let fog_amount = self.fog.amount(distance);
+
+        // The side of the wall facing the ray, and where along the wall it was hit,
+        // measured along the wall from a finite end
+        let surface = match &wall.material {
+            Some(material) => material.side(wall.is_front_facing(angle, &point)).clone(),
+            None => Surface::Color(wall.color.clone()),
+        };
+        let u = match (wall.beginning.is_ideal(), wall.end.is_ideal()) {
+            (false, _) => wall.length_along(&wall.beginning, &point),
+            (true, false) => -wall.length_along(&point, &wall.end),
+            (true, true) => 0.,
+        } / WALL_HEIGHT;
+
+        // Fix the calculated distance to correct the fisheye effect
+        let distance = self.projected_distance(distance, angle);
+
+        // Apply some lighting to the wall's color: the ambient light...
+        let mut illumination = Illumination::white(1.);
+
+        // Glowing walls aren't lit by anything
+        if wall.material.as_ref().is_some_and(|material| material.emissive) {
+            return Some(Hit::Wall {
+                shade: WallShade {
+                    surface,
+                    u,
+                    illumination: Illumination::white(1.),
+                    fog_amount,
+                    filter: LinearColor::white(),
+                },
+                distance,
+            });
+        }
+
+        // ...and the lights of the map reaching the hit point
+        match &wall.lightmap {
+            Some(lightmap) => {
+                let t = wall.beginning.distance_to(&point) / wall.beginning.distance_to(&wall.end);
+                let baked = sample_lightmap(lightmap, t);
+                illumination.red += baked.red;
+                illumination.green += baked.green;
+                illumination.blue += baked.blue;
+            }
+            None => add_lights_at(
+                &mut illumination,
+                &point,
+                &scene.lights,
+                &scene.walls,
+                Some(wall),
+            ),
+        }
+
+        Some(Hit::Wall {
+            shade: WallShade {
+                surface,
+                u,
+                illumination,
+                fog_amount,
+                filter: LinearColor::white(),
+            },
+            distance,
+        })
+    }
+
+    fn draw_hit(
+        &self,
+        hit: Option<Hit>,
+        angle: f64,
+        scene: &Scene,
+        column_buffer: &mut ColumnBuffer,
+        weight: f64,
+    ) {
+        let window_height = column_buffer.height() as f64;
+        let horizon = self.horizon(scene.player, window_height);
+
         match hit {
             // We did not found a wall, just draw an empty space
-            None => self.draw_wall(0.0, RGBColor::black(), canvas, column),
+            None => self.draw_wall(horizon, horizon, None, angle, scene, column_buffer, weight),
 
-            Some(Hit::Wall { color, distance }) => {
-				// Determine the visual height of the wall on the screen (normalized to the screen's height)
-				// 0.1 found by experiment. Works well with the scale of things on the Poincare disk coordinates
-                let normalized_wall_height = 0.1 / distance; //todo:: Allow different wall heights
+            // Walls at infinity are hidden in the fog
+            Some(Hit::Wall { distance, .. }) if !distance.is_finite() => {
+                self.draw_wall(horizon, horizon, None, angle, scene, column_buffer, weight)
+            }
+
+            Some(Hit::Wall { shade, distance }) => {
+                // Right at the wall, keep the wall's height finite
+                let distance = distance.max(MINIMUM_WALL_DISTANCE);
+
+				// Determine where the wall's top and bottom are on the screen: heights above the eyes shrink
+				// with the distance.
+                let eye_height = self.eye_height(scene.player);
+                let wall_top = self.row_of_height(scene.player, WALL_HEIGHT - eye_height, distance, window_height);
+                let wall_bottom = self.row_of_height(scene.player, -eye_height, distance, window_height);
 
                 // Finally: Draw the wall for the current view position…
-                self.draw_wall(normalized_wall_height, color, canvas, column)
+                self.draw_wall(wall_top, wall_bottom, Some(&shade), angle, scene, column_buffer, weight)
             }
         }
     }
@@ -131,33 +662,192 @@ impl Renderer {
     /// Draws a column of a wall for the given view position.
     ///
     ///	# Parameters:
-    ///  	- wall_height:	The visible height of a wall segment to be drawn (0: no wall, >=1: full view height).
-    ///  	- color:		The color of the wall to be drawn.
-    ///  	- canvas:		The canvas that should be used for drawing.
-    ///  	- column:		The current view column to be drawn.
-    fn draw_wall(&self, wall_height: f64, color: RGBColor, canvas: &mut Canvas, column: usize) {
-        let window_height = canvas.height();
-        let limited_wall_height = wall_height.min(1.0);
-        let view_wall_height = ((window_height as f64) * limited_wall_height) as usize;
+    ///     - wall_top:     The row where the wall starts, fractions for sub-pixel precision, may be off the screen.
+    ///     - wall_bottom:  The row where the wall ends, the same as `wall_top` if there is no wall.
+    ///     - shade:        The look of the wall to be drawn, `None` if there is no wall.
+    ///     - angle:        The angle of the ray, used to find the floor under each pixel.
+    ///     - scene:        The walls and lights lighting the floor.
+    ///     - column_buffer: The column being drawn.
+    ///     - weight:       The share of this ray in the column's final color.
+    #[allow(clippy::too_many_arguments)]
+    fn draw_wall(
+        &self,
+        wall_top: f64,
+        wall_bottom: f64,
+        shade: Option<&WallShade>,
+        angle: f64,
+        scene: &Scene,
+        column_buffer: &mut ColumnBuffer,
+        weight: f64,
+    ) {
+        let window_height = column_buffer.height();
+        let fog_color = LinearColor::from(&self.fog.color);
+
+        // Wall ends at sub-pixel precision, or rounded to whole rows
+        let (wall_top, wall_bottom) = if self.quality.smooth_edges {
+            (wall_top, wall_bottom)
+        } else {
+            (wall_top.round(), wall_bottom.round())
+        };
+
+        for y in 0..window_height {
+            let row_top = y as f64;
+            let row_bottom = row_top + 1.;
+
+            // How much of this row the ceiling, the wall and the floor cover
+            let ceiling_coverage = (wall_top.min(row_bottom) - row_top).max(0.);
+            let floor_coverage = (row_bottom - wall_bottom.max(row_top)).max(0.);
+            let wall_coverage = 1. - ceiling_coverage - floor_coverage;
+
+            // Draw the sky, hidden in the fog
+            if ceiling_coverage > 0. {
+                column_buffer.add(y, &fog_color, weight * ceiling_coverage);
+            }
+
+            // Draw the wall (if anything is visible), sampled at the middle of the row's part covered by it
+            if let Some(shade) = shade.filter(|_| wall_coverage > 0.) {
+                let middle = (wall_top.max(row_top) + wall_bottom.min(row_bottom)) / 2.;
+                let v = (middle - wall_top) / (wall_bottom - wall_top);
+                column_buffer.add(y, &shade.color_at(v, &fog_color), weight * wall_coverage);
+            }
+
+            // Draw the floor as grey gradient, getting darker with the distance of the floor under this pixel
+            if floor_coverage > 0. {
+                let gradient_position = (0.5 + self.player_height / self.floor_distance(scene.player, y, window_height)).min(1.);
+                let mut illumination = Illumination::white(gradient_position);
+
+                // Lit by the map lights reaching the floor under this pixel
+                if !scene.lights.is_empty() {
+                    let point = self.floor_point(scene.player, y, window_height, angle);
+                    add_lights_at(&mut illumination, &point, &scene.lights, &scene.walls, None);
+                }
+
+                let gradient_color = LinearColor::from(&RGBColor::dark_gray())
+                    .illuminate(&illumination)
+                    .mix(&fog_color, self.fog.amount(self.floor_ray_distance(scene.player, y, window_height, angle)));
+                column_buffer.add(y, &gradient_color, weight * floor_coverage);
+            }
+        }
+    }
+
+    /// Finds the point of the floor seen at the given row of a column.
+    /// Inverse of the wall height calculation: a wall bottom drawn at this row
+    /// would stand at this point.
+    fn floor_point(&self, player: &Player, y: usize, window_height: usize, angle: f64) -> PoincarePoint {
+        let distance = self.floor_ray_distance(player, y, window_height, angle);
+
+        // Point at the hyperbolic distance along the ray
+        let radius = (distance / 2.).tanh();
+        PoincarePoint::new(radius * angle.cos(), radius * angle.sin())
+    }
 
-        let wall_top = (window_height - view_wall_height) / 2;
-        let wall_bottom = wall_top + view_wall_height;
+    /// Finds the distance from the player to the point of the floor seen at the given row of a column,
+    /// undoing the fisheye correction of `floor_distance`.
+    fn floor_ray_distance(&self, player: &Player, y: usize, window_height: usize, angle: f64) -> f64 {
+        let projected_distance = self.floor_distance(player, y, window_height);
+        match self.view_mode {
+            ViewMode::Perspective => projected_distance / angle.cos(),
+            ViewMode::Panorama | ViewMode::Equirectangular => projected_distance,
+        }
+    }
 
-        // Draw the black ceiling
-        for y in 0..wall_top {
-            canvas.draw_pixel(column, y, &RGBColor::black());
+    /// Finds the projected distance of the floor seen at the given row, the inverse of `row_of_height`
+    /// for the floor.
+    fn floor_distance(&self, player: &Player, y: usize, window_height: usize) -> f64 {
+        let window_height = window_height as f64;
+        let below_horizon = (y as f64 - self.horizon(player, window_height)).max(1.);
+        match self.view_mode {
+            ViewMode::Perspective | ViewMode::Panorama => self.eye_height(player) * window_height / below_horizon,
+            ViewMode::Equirectangular => {
+                let below_angle = (below_horizon / window_height * std::f64::consts::PI)
+                    .min(std::f64::consts::FRAC_PI_2);
+                self.eye_height(player) / below_angle.tan()
+            }
         }
+    }
+
+    /// Draws the sprites of the map as billboards, from the farthest to the nearest one.
+    /// Columns where a wall is closer than the sprite are skipped, so sprites are not seen in mirrors.
+    ///
+    /// The sprite stands on the floor at its distance, like a wall does, but its height
+    /// shrinks with the circumference of a circle at that distance (sinh), so objects
+    /// become small exponentially fast as they move away.
+    fn draw_sprites(&self, map: &HyperMap, player: &Player, canvas: &mut Canvas, depth_buffer: &[f64]) {
+        let window_width = canvas.width() as f64;
+        let window_height = canvas.height() as f64;
 
-        // Draw the wall (if anything is visible)
-        for y in wall_top..wall_bottom {
-            canvas.draw_pixel(column as usize, y as usize, &color);
+        let mut visible: Vec<(f64, f64, &Sprite)> = map
+            .get_sprites_iter()
+            .filter_map(|sprite| {
+                let position: PoincarePoint = sprite.position.clone().into();
+                let (x, y) = (position.0[0], position.0[1]);
+
+                // Skip sprites behind the player, unless the view goes all around
+                if x <= 0. && self.view_mode == ViewMode::Perspective {
+                    return None;
+                }
+                let angle = y.atan2(x);
+                let distance = position.distance_to_origin();
+                Some((angle, distance, sprite))
+            })
+            .collect();
+        visible.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+
+        for (angle, distance, sprite) in visible {
+            let image = &sprite.image;
+            if image.width() == 0 || image.height() == 0 {
+                continue;
+            }
+
+            // Same fisheye correction as for the walls. The sprite is as high as a wall
+            // of this height would be, standing at its distance
+            let projected_distance = self.projected_distance(distance, angle);
+            let apparent_size = sprite.size * projected_distance / distance.sinh();
+            let eye_height = self.eye_height(player);
+            let bottom = self.row_of_height(player, -eye_height, projected_distance, window_height);
+            let top = self.row_of_height(player, apparent_size - eye_height, projected_distance, window_height);
+            let height = bottom - top;
+            let width = height * image.width() as f64 / image.height() as f64;
+            let center = self.ray_column(angle, canvas.width());
+
+            let left = center - width / 2.;
+            let first_column = left.max(0.) as usize;
+            let last_column = (left + width).min(window_width).max(0.) as usize;
+            let first_row = top.max(0.) as usize;
+            let last_row = bottom.min(window_height).max(0.) as usize;
+
+            for (column, depth) in depth_buffer
+                .iter()
+                .enumerate()
+                .take(last_column)
+                .skip(first_column)
+            {
+                if *depth <= projected_distance {
+                    continue;
+                }
+                let u = (column as f64 - left) / width;
+                for row in first_row..last_row {
+                    let v = (row as f64 - top) / height;
+                    if let Some(color) = image.sample(u, v) {
+                        canvas.draw_pixel(column, row, color);
+                    }
+                }
+            }
         }
+    }
 
-        // Draw the floor as grey gradient
-        for y in wall_bottom..window_height {
-            let gradient_position = y as f64 / window_height as f64;
-            let gradient_color = RGBColor::dark_gray().adjust_light_intensity(gradient_position);
-            canvas.draw_pixel(column as usize, y as usize, &gradient_color);
+    /// Height of the player's eyes above the floor, lowered by crouching and raised by jumping.
+    fn eye_height(&self, player: &Player) -> f64 {
+        player.eye_height(self.player_height)
+    }
+
+    /// The row of the horizon. Looking up or down shears the view vertically,
+    /// moving the horizon by the distance a ray at the pitch angle would move on the screen.
+    fn horizon(&self, player: &Player, window_height: f64) -> f64 {
+        let pitch = player.pitch();
+        match self.view_mode {
+            ViewMode::Perspective | ViewMode::Panorama => window_height / 2. + window_height * pitch.tan(),
+            ViewMode::Equirectangular => window_height / 2. + window_height * pitch / std::f64::consts::PI,
         }
     }
 
@@ -165,11 +855,54 @@ impl Renderer {
     /// The ray should be casted from the given player's using its position, viewing direction and the current focal length.
     ///
     ///	# Parameters:
-    ///		- column:	The current view column to be drawn (which must be less than the view's width).
+    ///		- column:	The current view column to be drawn (which must be less than the view's width), fractions for supersampling.
     ///		- width:	The width of the view.
-    fn ray_angle(&self, column: usize, max_column: usize) -> f64 {
-        let relative_position = ((column as f64) / (max_column as f64)) - 0.5;
-        let virtual_screen_position = relative_position * self.relative_screen_size;
-        return (virtual_screen_position / self.focal_length).atan();
+    fn ray_angle(&self, column: f64, max_column: usize) -> f64 {
+        let relative_position = (column / (max_column as f64)) - 0.5;
+        match self.view_mode {
+            ViewMode::Perspective => {
+                let virtual_screen_position = relative_position * self.relative_screen_size;
+                (virtual_screen_position / self.focal_length).atan()
+            }
+            ViewMode::Panorama | ViewMode::Equirectangular => relative_position * 2. * std::f64::consts::PI,
+        }
+    }
+
+    /// Inverse of `ray_angle`: determines the (fractional) view column in which
+    /// a ray with the given angle is drawn. Rays are cast through the middle of their column,
+    /// so a ray through the middle of a column is drawn at its whole number.
+    fn ray_column(&self, angle: f64, max_column: usize) -> f64 {
+        let relative_position = match self.view_mode {
+            ViewMode::Perspective => {
+                let virtual_screen_position = angle.tan() * self.focal_length;
+                virtual_screen_position / self.relative_screen_size
+            }
+            ViewMode::Panorama | ViewMode::Equirectangular => angle / (2. * std::f64::consts::PI),
+        };
+        (relative_position + 0.5) * max_column as f64 - 0.5
+    }
+
+    /// The distance used to scale things seen along a ray. The flat screen of the perspective
+    /// view needs the distance along the view direction, to correct the fisheye effect.
+    fn projected_distance(&self, distance: f64, angle: f64) -> f64 {
+        match self.view_mode {
+            ViewMode::Perspective => distance * angle.cos(),
+            ViewMode::Panorama | ViewMode::Equirectangular => distance,
+        }
+    }
+
+    /// The row where a point at the given height above the eyes (negative: below), seen at
+    /// the given projected distance, is drawn.
+    fn row_of_height(&self, player: &Player, height: f64, distance: f64, window_height: f64) -> f64 {
+        let horizon = self.horizon(player, window_height);
+        match self.view_mode {
+            // Heights shrink with the distance, in proportion to the screen's height
+            ViewMode::Perspective | ViewMode::Panorama => horizon - window_height * height / distance,
+
+            // The screen's height spans 180° of elevation
+            ViewMode::Equirectangular => {
+                horizon - window_height * height.atan2(distance) / std::f64::consts::PI
+            }
+        }
     }
 }
\ No newline at end of file
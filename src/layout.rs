@@ -0,0 +1,130 @@
+use crate::poincare_renderer::projection::Projection;
+
+/// The width of the gap between panes, in pixels.
+pub const GAP: usize = 2;
+
+/// How the window is divided into panes, each showing a view of the same game.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Layout {
+    /// The renderer in use fills the window.
+    Single,
+
+    /// The first-person view on the left, the top-down view with the player's view cone on the right.
+    SideBySide,
+
+    /// 2×2 top-down views in the Poincaré, Klein, band and half-plane models, to compare them.
+    Models,
+}
+
+/// What a pane shows.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum View {
+    /// The renderer in use, drawn as when it fills the window.
+    Active,
+
+    /// The first-person view.
+    FirstPerson,
+
+    /// The top-down view with the player's view cone, in the given projection,
+    /// or without one in the projection chosen for the top-down view.
+    TopDown(Option<Projection>),
+}
+
+/// A rectangle of the canvas showing a view.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Pane {
+    pub view: View,
+
+    /// The left edge, in pixels.
+    pub x: usize,
+
+    /// The top edge, in pixels.
+    pub y: usize,
+
+    pub width: usize,
+    pub height: usize,
+}
+
+impl Layout {
+    /// The layout to switch to next, cycling through all of them.
+    pub fn next(self) -> Layout {
+        match self {
+            Layout::Single => Layout::SideBySide,
+            Layout::SideBySide => Layout::Models,
+            Layout::Models => Layout::Single,
+        }
+    }
+
+    /// Reads a layout from its name on the command line: `single`, `side-by-side` or `models`.
+    pub fn from_name(name: &str) -> Option<Layout> {
+        match name {
+            "single" => Some(Layout::Single),
+            "side-by-side" => Some(Layout::SideBySide),
+            "models" => Some(Layout::Models),
+            _ => None,
+        }
+    }
+
+    /// The number of columns and rows of panes.
+    fn grid(self) -> (usize, usize) {
+        match self {
+            Layout::Single => (1, 1),
+            Layout::SideBySide => (2, 1),
+            Layout::Models => (2, 2),
+        }
+    }
+
+    /// The views of the panes, row by row from the top left.
+    fn views(self) -> Vec<View> {
+        match self {
+            Layout::Single => vec![View::Active],
+            Layout::SideBySide => vec![View::FirstPerson, View::TopDown(None)],
+            Layout::Models => vec![
+                View::TopDown(Some(Projection::Poincare)),
+                View::TopDown(Some(Projection::Klein)),
+                View::TopDown(Some(Projection::Band)),
+                View::TopDown(Some(Projection::HalfPlane)),
+            ],
+        }
+    }
+
+    /// Divides a canvas into the panes of the layout, a grid of nearly equal rectangles
+    /// with a gap of `GAP` pixels between them. Panes too small to draw in are left out.
+    ///
+    /// # Parameters:
+    ///     - width:        The width of the canvas, in pixels.
+    ///     - height:       The height of the canvas, in pixels.
+    pub fn panes(self, width: usize, height: usize) -> Vec<Pane> {
+        let (columns, rows) = self.grid();
+
+        // The start and the length of each column or row, along one side of the canvas
+        let split = |length: usize, parts: usize| -> Vec<(usize, usize)> {
+            (0..parts)
+                .map(|part| {
+                    let start = part * length / parts;
+                    let end = (part + 1) * length / parts;
+                    let gap = if part + 1 < parts { GAP } else { 0 };
+                    (start, (end - start).saturating_sub(gap))
+                })
+                .collect()
+        };
+        let (columns, rows) = (split(width, columns), split(height, rows));
+
+        self.views()
+            .into_iter()
+            .enumerate()
+            .map(|(index, view)| {
+                let (x, width) = columns[index % columns.len()];
+                let (y, height) = rows[index / columns.len()];
+                Pane {
+                    view,
+                    x,
+                    y,
+                    width,
+                    height,
+                }
+            })
+            .filter(|pane| pane.width > 0 && pane.height > 0)
+            .collect()
+    }
+}
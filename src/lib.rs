@@ -0,0 +1,15 @@
+//! A raycaster rendering a world in the hyperbolic plane.
+//!
+//! The binary shows it in a window. The library renders without one,
+//! see the `headless` module.
+
+pub mod game;
+pub mod headless;
+pub mod hud;
+pub mod hyperbolic_renderer;
+pub mod layout;
+pub mod poincare_renderer;
+pub mod recorder;
+pub mod renderer;
+pub mod utils;
+pub mod window;
@@ -1,34 +1,58 @@
 extern crate sdl2;
 
-use std::{cell::RefCell, panic};
+use std::{cell::RefCell, panic, time::{Duration, Instant}};
 
 mod emscripten;
-mod game;
-mod hyperbolic_renderer;
-mod poincare_renderer;
-mod utils;
-mod window;
-use game::hypermap::HyperMap;
-use game::Game;
-use hyperbolic_renderer::Renderer;
-//use poincare_renderer::Renderer;
+use hyperbolic_raycaster::game::hypermap::HyperMap;
+use hyperbolic_raycaster::game::Game;
+use hyperbolic_raycaster::headless::{self, Screenshot};
+use hyperbolic_raycaster::hud::Hud;
+use hyperbolic_raycaster::recorder::{self, Recorder, RecordingOptions};
+use hyperbolic_raycaster::renderer::Renderers;
+use hyperbolic_raycaster::window::event::Keycode;
+use hyperbolic_raycaster::window::resolution::ResolutionScaler;
+use hyperbolic_raycaster::window::Window;
+use hyperbolic_raycaster::window::{canvas::Canvas, event::Event};
 use std::process::exit;
-use window::event::Keycode;
-use window::Window;
-use window::{canvas::Canvas, event::Event};
 
 // "Globals", to solve the issue of Emscripten losing reference to these
 // when referenced by the closure called by emscripten main loop
 // (probably related to https://github.com/Rust-SDL2/rust-sdl2/issues/884 )
 thread_local! {
     pub static WINDOW: RefCell<Window> = RefCell::new(Window::new());
-    pub static RENDERER: RefCell<Renderer> = RefCell::new(Renderer::new(Game::new(HyperMap::new(include_str!("../assets/5square.json"))), 1.0, 0.75, 1., 0.25));
+    pub static GAME: RefCell<Game> = RefCell::new(Game::new(HyperMap::new(include_str!("../assets/5square.json"))));
+    pub static RENDERERS: RefCell<Renderers> = RefCell::new(GAME.with(|g| Renderers::new(&g.borrow(), 1.0, 0.75, 1., 0.25)));
     pub static INITIAL_RUN: RefCell<bool> = RefCell::new(true);
+    pub static RESOLUTION_SCALER: RefCell<ResolutionScaler> = RefCell::new(ResolutionScaler::new(30.0));
+    pub static LAST_UPDATE: RefCell<Instant> = RefCell::new(Instant::now());
+    pub static HUD: RefCell<Hud> = RefCell::new(Hud::new());
+    pub static RECORDING_OPTIONS: RefCell<RecordingOptions> = RefCell::new(RecordingOptions::default());
+    pub static RECORDER: RefCell<Option<Recorder>> = const { RefCell::new(None) };
 }
 
 fn main() {
     panic::set_hook(Box::new(console_error_panic_hook::hook));
 
+    // `render`: Save a picture without opening a window
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).map(String::as_str) == Some("render") {
+        render_headless(&args[2..]);
+        return;
+    }
+
+    // --record: Start recording right away
+    let recording_options = match RecordingOptions::from_args(&args[1..]) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("{}\n\n{}\n\n{}", e, recorder::USAGE, headless::USAGE);
+            exit(2);
+        }
+    };
+    if recording_options.path.is_some() {
+        toggle_recording(&recording_options);
+    }
+    RECORDING_OPTIONS.with(|o| *o.borrow_mut() = recording_options);
+
     #[cfg(not(target_os = "emscripten"))]
     {
         loop {
@@ -44,6 +68,47 @@ fn main() {
     }
 }
 
+/// Renders a picture as told by the arguments of the `render` subcommand and saves it.
+fn render_headless(args: &[String]) {
+    let screenshot = match Screenshot::from_args(args) {
+        Ok(screenshot) => screenshot,
+        Err(e) => {
+            eprintln!("{}\n\n{}", e, headless::USAGE);
+            exit(2);
+        }
+    };
+    if let Err(e) = screenshot.take() {
+        eprintln!("Error rendering {}: {}", screenshot.map.display(), e);
+        exit(1);
+    }
+}
+
+/// Starts recording if not recording yet, otherwise ends the recording.
+fn toggle_recording(options: &RecordingOptions) {
+    RECORDER.with(|r| {
+        let recording = r.borrow_mut().take();
+        match recording {
+            Some(recorder) => stop_recording(recorder),
+            None => match options.start() {
+                Ok(recorder) => {
+                    println!("Recording to {}", recorder.path().display());
+                    *r.borrow_mut() = Some(recorder);
+                }
+                Err(e) => println!("Error starting the recording: {}", e),
+            },
+        }
+    });
+}
+
+/// Ends a recording, writing out what is left of it.
+fn stop_recording(recorder: Recorder) {
+    let (frames, path) = (recorder.frames(), recorder.path().to_path_buf());
+    match recorder.finish() {
+        Ok(()) => println!("Recorded {} frames to {}", frames, path.display()),
+        Err(e) => println!("Error finishing the recording {}: {}", path.display(), e),
+    }
+}
+
 /// The main event handling loop.
 fn main_loop() {
     // Needs to be staggered like this because of Emscripten crashing
@@ -52,93 +117,240 @@ fn main_loop() {
         let mut window = w.borrow_mut();
         INITIAL_RUN.with(|w| {
             let initial_run = w.borrow_mut();
-            RENDERER.with(|w| {
-                let mut renderer = w.borrow_mut();
+            GAME.with(|g| RENDERERS.with(|r| {
+                let mut game = g.borrow_mut();
+                let mut renderers = r.borrow_mut();
 
                 // todo:: update initial run so it does not break Emscripten
                 // Right now it redraws every frame
                 let mut needs_refresh = *initial_run; 
 
                 // Get pending UI events
-                match window.event_source.poll_next_event() {
-                    None => {}
-                    Some(Event::Quit) => {
-                        exit(0);
-                    }
-                    Some(Event::Resize) => {
-                        needs_refresh = true;
+                while let Some(event) = window.event_source.poll_next_event() {
+                    match event {
+                        Event::Quit => {
+                            if let Some(recorder) = RECORDER.with(|r| r.borrow_mut().take()) {
+                                stop_recording(recorder);
+                            }
+                            exit(0);
+                        }
+                        Event::Resize => {
+                            needs_refresh = true;
+                        }
+
+                        // Q: Cycle rendering quality
+                        Event::KeyPressed(Keycode::Q) => {
+                            needs_refresh = true;
+                            renderers.hyperbolic.quality = renderers.hyperbolic.quality.next();
+                        }
+
+                        // V: Cycle the view mode (perspective, panorama, equirectangular)
+                        Event::KeyPressed(Keycode::V) => {
+                            needs_refresh = true;
+                            renderers.hyperbolic.view_mode = renderers.hyperbolic.view_mode.next();
+                        }
+
+                        // B: Cycle stereo rendering (off, side by side, red/cyan anaglyph)
+                        Event::KeyPressed(Keycode::B) => {
+                            needs_refresh = true;
+                            renderers.hyperbolic.stereo = renderers.hyperbolic.stereo.next();
+                        }
+
+                        // F: Cycle the fog model (linear, exponential, exponential squared, hyperbolic horizon)
+                        Event::KeyPressed(Keycode::F) => {
+                            needs_refresh = true;
+                            renderers.hyperbolic.fog.model = renderers.hyperbolic.fog.model.next();
+                        }
+
+                        // H: Toggle the heads-up display
+                        Event::KeyPressed(Keycode::H) => {
+                            needs_refresh = true;
+                            HUD.with(|h| {
+                                let mut hud = h.borrow_mut();
+                                hud.visible = !hud.visible;
+                            });
+                        }
+
+                        // G: Start/stop recording
+                        Event::KeyPressed(Keycode::G) => {
+                            RECORDING_OPTIONS.with(|o| toggle_recording(&o.borrow()));
+                        }
+
+                        // M: Toggle the minimap
+                        Event::KeyPressed(Keycode::M) => {
+                            needs_refresh = true;
+                            renderers.hyperbolic.minimap = !renderers.hyperbolic.minimap;
+                        }
+
+                        // Y: Cycle the ray debug view (off, every 16th, 4th, every ray cast drawn on the top-down view)
+                        Event::KeyPressed(Keycode::Y) => {
+                            needs_refresh = true;
+                            renderers.hyperbolic.debug_ray_step = match renderers.hyperbolic.debug_ray_step {
+                                0 => 16,
+                                16 => 4,
+                                4 => 1,
+                                _ => 0,
+                            };
+                        }
+
+                        // C: Recenter the top-down view on the point in the middle of it
+                        Event::KeyPressed(Keycode::C) => {
+                            needs_refresh = true;
+                            if let Some(viewport) = renderers.viewport_mut() {
+                                viewport.recenter();
+                            }
+                        }
+
+                        // R: Reset zoom, pan and centering of the top-down view
+                        Event::KeyPressed(Keycode::R) => {
+                            needs_refresh = true;
+                            if let Some(viewport) = renderers.viewport_mut() {
+                                viewport.reset();
+                            }
+                        }
+
+                        // P: Cycle the projection of the top-down view
+                        Event::KeyPressed(Keycode::P) => {
+                            needs_refresh = true;
+                            if let Some(viewport) = renderers.viewport_mut() {
+                                viewport.projection = viewport.projection.next();
+                            }
+                        }
+
+                        // Tab: Switch to the next renderer (first-person view, top-down view)
+                        Event::KeyPressed(Keycode::Tab) => {
+                            needs_refresh = true;
+                            renderers.kind = renderers.kind.next();
+                        }
+
+                        // T: Cycle the layout (one view, first-person and top-down side by side, 2×2 projections)
+                        Event::KeyPressed(Keycode::T) => {
+                            needs_refresh = true;
+                            renderers.layout = renderers.layout.next();
+                        }
+
+                        // Space: Jump
+                        Event::KeyPressed(Keycode::Space) => {
+                            needs_refresh = true;
+                            game.player.jump();
+                        }
+                        Event::KeyPressed(_) => {}
                     }
                 }
 
                 // Handle key presses
-                for keycode in window.event_source.pressed_keycodes() {
+                let pressed_keycodes = window.event_source.pressed_keycodes();
+
+                // Left Ctrl: Crouch while held
+                game.player.set_crouching(pressed_keycodes.iter().any(|keycode| matches!(keycode, Keycode::LCtrl)));
+
+                for keycode in pressed_keycodes {
                     needs_refresh = true;
                     let movement_speed = 0.01;
                     let rotation_speed = 0.01;
+                    let zoom_speed = 1.02;
+                    let pan_speed = 0.02;
+                    let pitch_speed = 0.01;
 
                     match keycode {
                         // Arrow up/down: Move player forward/backwards
-                        Keycode::Up => renderer.game.move_player(-movement_speed),
-                        Keycode::Down => renderer.game.move_player(movement_speed),
+                        Keycode::Up => game.move_player(-movement_speed),
+                        Keycode::Down => game.move_player(movement_speed),
 
                         // AWSD: Move player forward/backwards and strafe left/right
-                        Keycode::W => renderer.game.move_player(-movement_speed),
-                        Keycode::S => renderer.game.move_player(movement_speed),
-                        Keycode::D => renderer.game.strafe_player(movement_speed),
-                        Keycode::A => renderer.game.strafe_player(-movement_speed),
+                        Keycode::W => game.move_player(-movement_speed),
+                        Keycode::S => game.move_player(movement_speed),
+                        Keycode::D => game.strafe_player(movement_speed),
+                        Keycode::A => game.strafe_player(-movement_speed),
 
                         // Arrow right/left: Rotate player
                         Keycode::Right => {
-                            renderer.game.rotate_player(-rotation_speed);
+                            game.rotate_player(-rotation_speed);
                         }
                         Keycode::Left => {
-                            renderer.game.rotate_player(rotation_speed);
+                            game.rotate_player(rotation_speed);
+                        }
+
+                        // Page up/down: Look up/down
+                        Keycode::PageUp => game.player.look_up(pitch_speed),
+                        Keycode::PageDown => game.player.look_up(-pitch_speed),
+
+                        // Toggles are handled when the key is pressed
+                        _ => {}
+                    }
+
+                    // The top-down view of the renderer in use or of the split layout, or the minimap
+                    if let Some(viewport) = renderers.viewport_mut() {
+                        match keycode {
+                            // Z/X: Zoom the top-down view in/out
+                            Keycode::Z => viewport.zoom_by(zoom_speed),
+                            Keycode::X => viewport.zoom_by(1. / zoom_speed),
+
+                            // IJKL: Pan the top-down view
+                            Keycode::I => viewport.pan_by(0., -pan_speed),
+                            Keycode::K => viewport.pan_by(0., pan_speed),
+                            Keycode::J => viewport.pan_by(-pan_speed, 0.),
+                            Keycode::L => viewport.pan_by(pan_speed, 0.),
+
+                            _ => {}
                         }
                     }
                 }
 
+                // While recording, every frame is recorded and moves the world on by the same step,
+                // so the recording does not depend on how long frames take to render
+                let frame_step = RECORDER.with(|r| r.borrow().as_ref().map(Recorder::frame_step));
+                if frame_step.is_some() {
+                    needs_refresh = true;
+                }
+
+                // Let the player fall back to the floor, crouch and stand up
+                let since_last_update = LAST_UPDATE.with(|u| u.replace(Instant::now()).elapsed());
+                let elapsed = frame_step.unwrap_or(since_last_update);
+                if game.player.is_moving() {
+                    needs_refresh = true;
+                }
+                game.player.update(elapsed);
+
                 // Refresh screen if needed
                 if needs_refresh {
-                    let width = window.view.canvas.window().size().0;
-                    let height = window.view.canvas.window().size().1;
-
-                    let texture_creator = window.view.canvas.texture_creator();
-                    let mut texture = match texture_creator.create_texture_streaming(
-                        sdl2::pixels::PixelFormatEnum::RGB24,
-                        width,
-                        height,
-                    ) {
-                        Ok(tex) => {
-                            tex
-                        }
-                        Err(e) => {
-                            println!("Error creating texture! {}", e.to_string());
-                            panic!("Error creating texture!");
-                        }
-                    };
-                    texture
-                        .with_lock(None, |buffer: &mut [u8], pitch: usize| {
-                            renderer.render(&mut Canvas::new(
-                                buffer,
-                                pitch,
-                                width as usize,
-                                height as usize,
-                            ));
-                        })
-                        .unwrap();
-                    match window.view.canvas.copy(&texture, None, None) {
-                        Ok(tex) => {
-                            tex
-                        }
-                        Err(e) => {
-                            println!("Error presenting texture! {}", e.to_string());
-                            panic!("rror presenting texture!");
-                        }
+                    // Render at a lowered resolution if frames take too long, scaled up to the window when copied.
+                    // Recordings keep the full resolution.
+                    let (width, height) = match frame_step {
+                        Some(_) => window.view.canvas.window().size(),
+                        None => RESOLUTION_SCALER.with(|s| {
+                            s.borrow().scaled_size(
+                                window.view.canvas.window().size().0,
+                                window.view.canvas.window().size().1,
+                            )
+                        }),
                     };
 
+                    let mut render_time = Duration::ZERO;
+                    window.view.draw_frame(width, height, |buffer: &mut [u8], pitch: usize| {
+                        let mut canvas = Canvas::new(buffer, pitch, width as usize, height as usize);
+                        let render_start = Instant::now();
+                        renderers.render(&game, &mut canvas);
+                        render_time = render_start.elapsed();
+
+                        // Recordings show the world without the HUD over it
+                        RECORDER.with(|r| {
+                            let mut recorder = r.borrow_mut();
+                            if let Some(Err(e)) = recorder.as_mut().map(|recorder| recorder.record(&canvas)) {
+                                println!("Error recording, stopped: {}", e);
+                                *recorder = None;
+                            }
+                        });
+                        HUD.with(|h| h.borrow().draw(&game, renderers.rays_cast(), &mut canvas));
+                    });
+
                     window.view.canvas.present();
+                    RESOLUTION_SCALER.with(|s| s.borrow_mut().update(render_time));
+                    HUD.with(|h| h.borrow_mut().record_frame(render_time));
+                } else {
+                    RESOLUTION_SCALER.with(|s| s.borrow_mut().idle(since_last_update));
                 }
-            });
+            }));
         });
     });
 }
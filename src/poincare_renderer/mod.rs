@@ -1,13 +1,17 @@
+pub mod projection;
+pub mod viewport;
+
+use crate::hyperbolic_renderer::DebugRay;
 use crate::utils::color::RGBColor;
+use crate::renderer;
+use crate::utils::poincarepoint::PoincarePoint;
 use crate::window::canvas::Canvas;
 use crate::{game::Game, utils::poincarepoint::PoincareWall};
 use line_drawing::Bresenham;
+use viewport::Viewport;
 
-/// Draws a top-down view on a Poincare disk.
+/// Draws a top-down view on a Poincare disk, or another model of the hyperbolic plane.
 pub struct Renderer {
-    /// The state of the virtual world to be rendered
-    pub game: Game,
-
     /// The size of the physical computer display in relation to a grid field
     pub relative_screen_size: f64,
 
@@ -19,75 +23,211 @@ pub struct Renderer {
 
     /// The minimum environment light of the scene
     pub minimum_light: f64,
+
+    /// The zoomed, panned and recentered part of the disk that is shown
+    pub viewport: Viewport,
 }
 
 impl Renderer {
-    /// Initializes the renderer with a map, a player and a focal length that should be used for rendering.
+    /// Initializes the renderer with a focal length that should be used for rendering.
     ///
     /// # Parameters:
-    /// 	- game:						The virtual world state (i.e. the game's map and player position)
     ///		- relative_screen_size:		The size of the physical computer display in relation to a grid field
     ///  	- focal_length:				A focal length that should be used for rendering.
     ///	 	- illumination_radius:		The radius around the player where objects should appear illuminated.
     ///	 	- minimum_öight:			The minimum environment light of the scene.
     ///
     pub fn new(
-        game: Game,
         relative_screen_size: f64,
         focal_length: f64,
         illumination_radius: f64,
         minimum_light: f64,
     ) -> Renderer {
         Renderer {
-            game,
             relative_screen_size,
             focal_length,
             illumination_radius,
             minimum_light,
+            viewport: Viewport::default(),
         }
     }
 
-    /// Renders one frame into a canvas.
-    ///
-    /// # Parameters:
-    ///		- canvas		The canvas that should be drawn to.
-    pub fn render(&self, canvas: &mut Canvas) {
-        self.game
-            .map
+    /// Draws the walls of a game's map as seen through a viewport, in its projection.
+    /// Also used for the minimap of the first-person view.
+    pub fn draw_map(game: &Game, viewport: &Viewport, canvas: &mut Canvas) {
+        Self::draw_boundary(viewport, canvas);
+        game.map
             .get_walls_as_poincare()
             .iter()
-            .map(|w| w.clone().into())
-            .for_each(|wall: PoincareWall| {
-                self.draw_wall(&wall, canvas);
+            .for_each(|wall: &PoincareWall| {
+                Self::draw_wall(wall, viewport, canvas);
             });
     }
 
-    /// Draws wall as a line on the Poincare disk model.
-    fn draw_wall(&self, wall: &PoincareWall, canvas: &mut Canvas) {
-        let start =
-            self.translate_to_canvas_coords(wall.beginning.0[0], wall.beginning.0[1], canvas);
-        let end = self.translate_to_canvas_coords(wall.end.0[0], wall.end.0[1], canvas);
+    /// Draws the player at the center of the disk, with the cone of rays seen by the player
+    /// between the given angles. The rays are geodesics, straight lines unless the viewport is recentered.
+    pub fn draw_view_cone(
+        left_angle: f64,
+        right_angle: f64,
+        viewport: &Viewport,
+        canvas: &mut Canvas,
+    ) {
+        for angle in &[left_angle, right_angle] {
+            let ray = Self::ray(Self::boundary_point(*angle), RGBColor::yellow());
+            Self::draw_wall(&ray, viewport, canvas);
+        }
+
+        let player = viewport.transform(&PoincarePoint::new(0., 0.));
+        Self::draw_point_of_a_disc(&player, &RGBColor::orange(), viewport, canvas);
+    }
+
+    /// Draws rays recorded by the first-person view, each as its geodesic from the player.
+    /// A ray that hit a wall is drawn in a darker shade of the wall's color, with a dot of the
+    /// wall's color where it hit. A ray that hit nothing is drawn in gray up to the boundary,
+    /// where it is marked with a white dot.
+    pub fn draw_rays(rays: &[DebugRay], viewport: &Viewport, canvas: &mut Canvas) {
+        for debug_ray in rays {
+            let (end, color) = match &debug_ray.hit {
+                Some((point, color)) => (point.clone(), color.clone()),
+                None => (Self::boundary_point(debug_ray.angle), RGBColor::white()),
+            };
+            let line_color = match debug_ray.hit {
+                Some(_) => color.adjust_light_intensity(0.3),
+                None => RGBColor::dark_gray(),
+            };
 
-        for (x, y) in Bresenham::new(start, end) {
-            canvas.draw_pixel(x as usize, y as usize, &wall.color);
+            Self::draw_wall(&Self::ray(end.clone(), line_color), viewport, canvas);
+            Self::draw_point_of_a_disc(&viewport.transform(&end), &color, viewport, canvas);
         }
     }
 
-    ///expects x and y between -1:1
-    fn draw_point_of_a_disc(&self, x: f64, y: f64, color: &RGBColor, canvas: &mut Canvas) {
-        let (output_x, output_y) = self.translate_to_canvas_coords(x, y, canvas);
-        canvas.draw_pixel_big(output_x as usize, output_y as usize, &color);
+    /// The point a ray from the center of the disk in the given direction meets the boundary.
+    /// The boundary is infinitely far away, so the point is just before it.
+    fn boundary_point(angle: f64) -> PoincarePoint {
+        PoincarePoint::new(0.999 * angle.cos(), 0.999 * angle.sin())
     }
 
-    /// todo:: Consider the size of the canvas.
-    fn translate_to_canvas_coords(&self, x: f64, y: f64, canvas: &Canvas) -> (i32, i32) {
-        //let window_height = canvas.height();
-        //let window_width = canvas.width();
-        //let left_pad = (window_width - window_height) / 2;
+    /// A geodesic from the center of the disk to a point, to be drawn like a wall.
+    fn ray(end: PoincarePoint, color: RGBColor) -> PoincareWall {
+        PoincareWall {
+            beginning: PoincarePoint::new(0., 0.),
+            end,
+            color,
+            material_name: None,
+            material: None,
+            reflective: false,
+            lightmap: None,
+            curvature: 0.,
+            through: None,
+        }
+    }
+
+    /// Draws wall as its geodesic or curve, an arc on the Poincare disk model, and the matching curve in other projections.
+    fn draw_wall(wall: &PoincareWall, viewport: &Viewport, canvas: &mut Canvas) {
+        let transformed = wall.map_points(|point| viewport.transform(point));
+
+        let points: Vec<Option<(f64, f64)>> = transformed
+            .points_along(viewport.pixel_size(canvas))
+            .iter()
+            .map(|point| viewport.to_canvas(point, canvas))
+            .collect();
+
+        Self::draw_polyline(&points, &wall.color, canvas);
+    }
+
+    /// Draws the boundary of the plane, the points at infinity.
+    fn draw_boundary(viewport: &Viewport, canvas: &mut Canvas) {
+        let steps = (2. * std::f64::consts::PI / viewport.pixel_size(canvas)).ceil() as usize;
+        for curve in viewport.projection.boundary(viewport.extent(canvas), steps) {
+            let points: Vec<Option<(f64, f64)>> = curve
+                .iter()
+                .map(|&(x, y)| Some(viewport.plane_to_canvas(x, y, canvas)))
+                .collect();
+
+            Self::draw_polyline(&points, &RGBColor::dark_gray(), canvas);
+        }
+    }
+
+    /// Connects consecutive canvas points with lines, leaving gaps at missing points.
+    fn draw_polyline(points: &[Option<(f64, f64)>], color: &RGBColor, canvas: &mut Canvas) {
+        for segment in points.windows(2) {
+            if let (Some(start), Some(end)) = (segment[0], segment[1]) {
+                if let Some((start, end)) = Self::clip_to_canvas(start, end, canvas) {
+                    for (x, y) in Bresenham::new(start, end) {
+                        canvas.try_draw_pixel(x, y, color);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Cuts the part of a line segment that lies on the canvas (Liang-Barsky),
+    /// so that far away points of the unbounded projections are cheap to draw.
+    fn clip_to_canvas(
+        start: (f64, f64),
+        end: (f64, f64),
+        canvas: &Canvas,
+    ) -> Option<((i32, i32), (i32, i32))> {
+        let (dx, dy) = (end.0 - start.0, end.1 - start.1);
+        let (max_x, max_y) = (canvas.width() as f64, canvas.height() as f64);
+        let (mut t0, mut t1) = (0f64, 1f64);
+
+        let edges = [
+            (-dx, start.0),
+            (dx, max_x - start.0),
+            (-dy, start.1),
+            (dy, max_y - start.1),
+        ];
+        for &(p, q) in &edges {
+            if p == 0. {
+                if q < 0. {
+                    return None;
+                }
+            } else if p < 0. {
+                t0 = t0.max(q / p);
+            } else {
+                t1 = t1.min(q / p);
+            }
+        }
+        if t0 > t1 {
+            return None;
+        }
+
+        Some((
+            ((start.0 + t0 * dx) as i32, (start.1 + t0 * dy) as i32),
+            ((start.0 + t1 * dx) as i32, (start.1 + t1 * dy) as i32),
+        ))
+    }
+
+    /// Draws a big dot at an already transformed point of the disk.
+    fn draw_point_of_a_disc(
+        point: &PoincarePoint,
+        color: &RGBColor,
+        viewport: &Viewport,
+        canvas: &mut Canvas,
+    ) {
+        let (output_x, output_y) = match viewport.to_canvas(point, canvas) {
+            Some((x, y)) => (x as i32, y as i32),
+            None => return,
+        };
+        // Leave room for the neighbouring pixels of the dot
+        if output_x < 1
+            || output_y < 1
+            || output_x + 1 >= canvas.width() as i32
+            || output_y + 1 >= canvas.height() as i32
+        {
+            return;
+        }
+        canvas.draw_pixel_big(output_x as usize, output_y as usize, color);
+    }
+}
+
+impl renderer::Renderer for Renderer {
+    fn render(&self, game: &Game, canvas: &mut Canvas) {
+        Self::draw_map(game, &self.viewport, canvas);
+    }
 
-        (
-            ((x + 1.0) * 250.0) as i32 + 150,
-            ((y + 1.0) * 250.0) as i32 + 30,
-        )
+    fn viewport_mut(&mut self) -> Option<&mut Viewport> {
+        Some(&mut self.viewport)
     }
 }
@@ -0,0 +1,226 @@
+use nalgebra::Complex;
+
+use crate::utils::poincarepoint::PoincarePoint;
+
+/// Models of the hyperbolic plane the top-down view can be drawn in.
+/// Each maps the Poincare disk onto the plane of the screen, in units
+/// where the disk models have radius 1.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Projection {
+    /// Conformal, geodesics are circle arcs orthogonal to the boundary.
+    Poincare,
+
+    /// Beltrami-Klein disk, geodesics are straight lines.
+    Klein,
+
+    /// Conformal band model: the disk is stretched into a horizontal strip,
+    /// with the player's line of sight along its middle.
+    Band,
+
+    /// Gans model: the hyperboloid seen from above, unbounded.
+    Gans,
+
+    /// Conformal upper half-plane, with the point straight ahead of the player at infinity, upwards.
+    HalfPlane,
+}
+
+impl Projection {
+    /// The projection to switch to next, cycling through all of them.
+    pub fn next(self) -> Projection {
+        match self {
+            Projection::Poincare => Projection::Klein,
+            Projection::Klein => Projection::Band,
+            Projection::Band => Projection::Gans,
+            Projection::Gans => Projection::HalfPlane,
+            Projection::HalfPlane => Projection::Poincare,
+        }
+    }
+
+    /// The name of the model, in capitals for the built-in font.
+    pub fn name(self) -> &'static str {
+        match self {
+            Projection::Poincare => "POINCARE DISK",
+            Projection::Klein => "KLEIN DISK",
+            Projection::Band => "BAND",
+            Projection::Gans => "GANS",
+            Projection::HalfPlane => "HALF-PLANE",
+        }
+    }
+
+    /// Whether distances far from the center are stretched out to infinity, so
+    /// curves have to be sampled more densely to look smooth.
+    pub fn is_unbounded(self) -> bool {
+        match self {
+            Projection::Poincare | Projection::Klein => false,
+            Projection::Band | Projection::Gans | Projection::HalfPlane => true,
+        }
+    }
+
+    /// Maps a point of the Poincare disk to the screen plane.
+    /// Returns `None` for points the projection sends to infinity.
+    pub fn project(self, point: &PoincarePoint) -> Option<(f64, f64)> {
+        let z = Complex::new(point.0[0], point.0[1]);
+        let one = Complex::new(1., 0.);
+        let norm_sqr = z.norm_sqr();
+
+        let projected = match self {
+            Projection::Poincare => z,
+            Projection::Klein => z * (2. / (1. + norm_sqr)),
+            Projection::Band => {
+                // 4/PI * artanh(z), artanh maps the disk onto the strip |Im| < PI/4
+                let w: Complex<f64> = (one + z) / (one - z);
+                Complex::new(w.norm_sqr().ln() / 2., w.im.atan2(w.re)) * (2. / std::f64::consts::PI)
+            }
+            Projection::Gans => z * (2. / (1. - norm_sqr)),
+            Projection::HalfPlane => {
+                // The Cayley transform, moved and scaled so the center of the disk
+                // stays in the middle and the boundary is at y = 0.5 (the screen's Y axis points down)
+                let w = Complex::new(0., 1.) * (one + z) / (one - z);
+                Complex::new(w.re / 2., (1. - w.im) / 2.)
+            }
+        };
+
+        match projected.re.is_finite() && projected.im.is_finite() {
+            true => Some((projected.re, projected.im)),
+            false => None,
+        }
+    }
+
+    /// Maps a point of the screen plane back to the Poincare disk.
+    /// Returns `None` for points outside of the projected plane.
+    pub fn unproject(self, (x, y): (f64, f64)) -> Option<PoincarePoint> {
+        let p = Complex::new(x, y);
+        let i = Complex::new(0., 1.);
+
+        let z = match self {
+            Projection::Poincare => p,
+            Projection::Klein if p.norm_sqr() < 1. => p / (1. + (1. - p.norm_sqr()).sqrt()),
+            Projection::Klein => return None,
+            Projection::Band if y.abs() < 1. => {
+                // tanh(PI/4 * p) = (e^(PI/2 * p) - 1) / (e^(PI/2 * p) + 1)
+                let (re, im) = (
+                    x * std::f64::consts::FRAC_PI_2,
+                    y * std::f64::consts::FRAC_PI_2,
+                );
+                let exp = Complex::new(im.cos(), im.sin()) * re.exp();
+                (exp - 1.) / (exp + 1.)
+            }
+            Projection::Band => return None,
+            Projection::Gans => p / (1. + (1. + p.norm_sqr()).sqrt()),
+            Projection::HalfPlane => {
+                let w = Complex::new(2. * x, 1. - 2. * y);
+                if w.im <= 0. {
+                    return None;
+                }
+                (w - i) / (w + i)
+            }
+        };
+
+        match z.norm_sqr() < 1. {
+            true => Some(PoincarePoint::new(z.re, z.im)),
+            false => None,
+        }
+    }
+
+    /// Curves of the points at infinity, in screen plane coordinates.
+    /// Lines of the unbounded projections reach `extent` away from the center.
+    pub fn boundary(self, extent: f64, steps: usize) -> Vec<Vec<(f64, f64)>> {
+        match self {
+            Projection::Poincare | Projection::Klein => vec![(0..=steps)
+                .map(|step| {
+                    let angle = 2. * std::f64::consts::PI * step as f64 / steps as f64;
+                    (angle.cos(), angle.sin())
+                })
+                .collect()],
+            Projection::Band => vec![
+                vec![(-extent, -1.), (extent, -1.)],
+                vec![(-extent, 1.), (extent, 1.)],
+            ],
+            Projection::Gans => vec![],
+            Projection::HalfPlane => vec![vec![(-extent, 0.5), (extent, 0.5)]],
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ALL: [Projection; 5] = [
+        Projection::Poincare,
+        Projection::Klein,
+        Projection::Band,
+        Projection::Gans,
+        Projection::HalfPlane,
+    ];
+
+    /// Points all over the disk, from the center to close to the boundary.
+    fn points() -> Vec<PoincarePoint> {
+        let mut points = vec![PoincarePoint::new(0., 0.)];
+        for radius in &[0.1, 0.5, 0.9, 0.99] {
+            for step in 0..12 {
+                let angle = 2. * std::f64::consts::PI * step as f64 / 12. + 0.1;
+                points.push(PoincarePoint::new(radius * angle.cos(), radius * angle.sin()));
+            }
+        }
+        points
+    }
+
+    #[test]
+    fn unproject_undoes_project() {
+        for projection in &ALL {
+            for point in points() {
+                let projected = projection.project(&point).unwrap();
+                let unprojected = projection.unproject(projected).unwrap();
+                assert!(
+                    (unprojected.0 - point.0).norm() < 1e-9,
+                    "{:?}: {:?} comes back as {:?}",
+                    projection,
+                    point,
+                    unprojected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn project_undoes_unproject() {
+        for projection in &ALL {
+            for x in -10..=10 {
+                for y in -10..=10 {
+                    let screen = (x as f64 * 0.15, y as f64 * 0.15);
+                    let reprojected = match projection.unproject(screen) {
+                        Some(point) => projection.project(&point).unwrap(),
+                        None => continue,
+                    };
+                    assert!(
+                        (reprojected.0 - screen.0).abs() < 1e-9 && (reprojected.1 - screen.1).abs() < 1e-9,
+                        "{:?}: {:?} comes back as {:?}",
+                        projection,
+                        screen,
+                        reprojected
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn center_of_the_disk_stays_in_the_middle() {
+        for projection in &ALL {
+            let (x, y) = projection.project(&PoincarePoint::new(0., 0.)).unwrap();
+            assert!(x.abs() < 1e-12 && y.abs() < 1e-12, "{:?}: ({}, {})", projection, x, y);
+        }
+    }
+
+    #[test]
+    fn points_outside_of_the_projected_plane_are_not_unprojected() {
+        assert!(Projection::Poincare.unproject((0.8, 0.8)).is_none());
+        assert!(Projection::Klein.unproject((0.8, 0.8)).is_none());
+        assert!(Projection::Band.unproject((0., 1.5)).is_none());
+        assert!(Projection::HalfPlane.unproject((0., 1.)).is_none());
+
+        // The Gans model covers the whole plane
+        assert!(Projection::Gans.unproject((100., -100.)).is_some());
+    }
+}
@@ -0,0 +1,125 @@
+use super::projection::Projection;
+use crate::utils::poincarepoint::PoincarePoint;
+use crate::window::canvas::Canvas;
+
+/// The part of the Poincare disk shown by the top-down view.
+///
+/// Before being drawn, points are moved by the isometry of the hyperbolic plane
+/// which brings `center` to the middle of the disk, so far regions of a map can be
+/// seen without the distortion near the boundary. The result is then drawn in one of
+/// the models of the hyperbolic plane, zoomed and panned like a flat picture.
+#[derive(Clone, Debug)]
+pub struct Viewport {
+    /// The point of the disk moved to the middle of the view.
+    center: PoincarePoint,
+
+    /// Magnification, 1 fits the whole disk into the canvas.
+    zoom: f64,
+
+    /// The point shown in the middle of the canvas, in coordinates of the projection.
+    pan: (f64, f64),
+
+    /// The model of the hyperbolic plane the disk is drawn in.
+    pub projection: Projection,
+}
+
+impl Default for Viewport {
+    fn default() -> Self {
+        Viewport {
+            center: PoincarePoint::new(0., 0.),
+            zoom: 1.,
+            pan: (0., 0.),
+            projection: Projection::Poincare,
+        }
+    }
+}
+
+impl Viewport {
+    /// The smallest magnification, the disk is then a quarter of the canvas.
+    pub const MINIMUM_ZOOM: f64 = 0.25;
+
+    /// Magnifies the view by a factor, keeping the middle of the canvas in place.
+    pub fn zoom_by(&mut self, factor: f64) {
+        self.zoom = (self.zoom * factor).max(Self::MINIMUM_ZOOM);
+    }
+
+    /// Moves the view, by a fraction of the canvas radius.
+    pub fn pan_by(&mut self, dx: f64, dy: f64) {
+        self.pan.0 += dx / self.zoom;
+        self.pan.1 += dy / self.zoom;
+    }
+
+    /// Moves the point in the middle of the canvas to the middle of the disk, through an isometry.
+    /// Zoom is kept, pan is reset. Does nothing if the middle of the canvas is outside of the plane.
+    pub fn recenter(&mut self) {
+        let middle = match self.projection.unproject(self.pan) {
+            Some(point) => point,
+            None => return,
+        };
+
+        // The boundary of the disk is infinitely far away, stay just inside
+        let (x, y) = (middle.0[0], middle.0[1]);
+        let scale = 0.99 / (x.powi(2) + y.powi(2)).sqrt().max(0.99);
+        let middle = PoincarePoint::new(x * scale, y * scale);
+
+        let inverse_center = PoincarePoint::new(-self.center.0[0], -self.center.0[1]);
+        self.center = middle.transform_to_origin(&inverse_center, 0.);
+        self.pan = (0., 0.);
+    }
+
+    /// Goes back to the whole disk, centered at the player. The projection is kept.
+    pub fn reset(&mut self) {
+        *self = Viewport {
+            projection: self.projection,
+            ..Viewport::default()
+        };
+    }
+
+    /// Moves a point of the disk by the isometry bringing `center` to the middle.
+    pub fn transform(&self, point: &PoincarePoint) -> PoincarePoint {
+        point.transform_to_origin(&self.center, 0.)
+    }
+
+    /// Maps an already transformed point of the disk to the canvas.
+    /// Returns `None` for points the projection sends to infinity.
+    pub fn to_canvas(&self, point: &PoincarePoint, canvas: &Canvas) -> Option<(f64, f64)> {
+        self.projection
+            .project(point)
+            .map(|(x, y)| self.plane_to_canvas(x, y, canvas))
+    }
+
+    /// Maps a point of the projection's plane to the canvas, fitting the disk models into the canvas.
+    pub fn plane_to_canvas(&self, x: f64, y: f64, canvas: &Canvas) -> (f64, f64) {
+        let window_height = canvas.height() as f64;
+        let window_width = canvas.width() as f64;
+        let radius = self.radius(canvas);
+
+        (
+            window_width / 2. + (x - self.pan.0) * radius,
+            window_height / 2. + (y - self.pan.1) * radius,
+        )
+    }
+
+    /// How far from the middle of the canvas its corners are, in coordinates of the projection.
+    pub fn extent(&self, canvas: &Canvas) -> f64 {
+        let half_diagonal = (canvas.width().pow(2) + canvas.height().pow(2)) as f64 / 4.;
+        half_diagonal.sqrt() / self.radius(canvas).max(1.) + self.pan.0.abs() + self.pan.1.abs()
+    }
+
+    /// The size of a canvas pixel in coordinates of the disk, at its middle.
+    /// Curves are sampled more densely where the projection stretches the plane.
+    pub fn pixel_size(&self, canvas: &Canvas) -> f64 {
+        let size = 1. / self.radius(canvas).max(1.);
+        match self.projection.is_unbounded() {
+            true => size / 16.,
+            false => size,
+        }
+    }
+
+    /// The radius of the disk on the canvas, in pixels.
+    fn radius(&self, canvas: &Canvas) -> f64 {
+        let window_height = canvas.height() as f64;
+        let window_width = canvas.width() as f64;
+        (window_width.min(window_height) - 1.) / 2. * self.zoom
+    }
+}
@@ -0,0 +1,261 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::headless::Frame;
+use crate::window::canvas::Canvas;
+
+/// How to use the recording options of the binary.
+pub const USAGE: &str = "Usage: hyperbolic-raycaster [options]
+
+Options:
+    --record <path>           Record from the start: to an animated GIF if the path ends
+                              with .gif, otherwise to numbered PNG files in that directory
+    --frame-step <seconds>    Time the world moves on by between recorded frames (default 0.04)";
+
+/// What a recording is written as.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RecordingFormat {
+    /// Numbered PNG files in a directory: `frame_00000.png`, `frame_00001.png`, …
+    PngSequence,
+
+    /// One animated GIF file, playing in a loop.
+    Gif,
+}
+
+impl RecordingFormat {
+    /// The format of a recording to the given path: a GIF for `.gif` files, PNG files for anything else.
+    pub fn for_path(path: &Path) -> RecordingFormat {
+        match path.extension() {
+            Some(extension) if extension.eq_ignore_ascii_case("gif") => RecordingFormat::Gif,
+            _ => RecordingFormat::PngSequence,
+        }
+    }
+}
+
+/// Where and how fast to record, as given on the command line.
+#[derive(Clone, Debug, PartialEq)]
+pub struct RecordingOptions {
+    /// Where to record to from the start. Without it, recordings are started with a key
+    /// and go to `recording-<n>.gif`.
+    pub path: Option<PathBuf>,
+
+    /// The time the world moves on by between recorded frames, however long rendering them takes.
+    pub frame_step: Duration,
+}
+
+impl RecordingOptions {
+    /// Reads the recording options from the arguments of the binary.
+    /// Returns a message telling what is wrong with them if they are not valid.
+    pub fn from_args(args: &[String]) -> Result<RecordingOptions, String> {
+        let mut options = RecordingOptions::default();
+
+        let mut args = args.iter();
+        while let Some(arg) = args.next() {
+            let mut value = || args.next().ok_or(format!("Missing value of {}", arg));
+            match arg.as_str() {
+                "--record" => options.path = Some(PathBuf::from(value()?)),
+                "--frame-step" => {
+                    let seconds = value()?;
+                    options.frame_step = seconds
+                        .parse()
+                        .ok()
+                        .filter(|&seconds: &f64| seconds > 0. && seconds.is_finite())
+                        .map(Duration::from_secs_f64)
+                        .ok_or(format!("Invalid frame step: {}", seconds))?;
+                }
+                option => return Err(format!("Unknown option: {}", option)),
+            }
+        }
+        Ok(options)
+    }
+
+    /// Starts a recording to the given path, or to the first `recording-<n>.gif` in the
+    /// working directory that does not exist yet.
+    pub fn start(&self) -> io::Result<Recorder> {
+        let path = match &self.path {
+            Some(path) => path.clone(),
+            None => (1..)
+                .map(|n| PathBuf::from(format!("recording-{}.gif", n)))
+                .find(|path| !path.exists())
+                .unwrap(),
+        };
+        Recorder::new(path, self.frame_step)
+    }
+}
+
+impl Default for RecordingOptions {
+    /// No recording from the start, 25 recorded frames per second.
+    fn default() -> Self {
+        RecordingOptions {
+            path: None,
+            frame_step: Duration::from_millis(40),
+        }
+    }
+}
+
+/// Writes rendered frames to numbered PNG files or an animated GIF.
+///
+/// All frames of a recording have the size of the first one: later frames of another size
+/// (e.g. after the window was resized) are cut off or filled up with black.
+pub struct Recorder {
+    path: PathBuf,
+    format: RecordingFormat,
+    frame_step: Duration,
+
+    /// Size of the recorded frames, known from the first frame.
+    size: Option<(usize, usize)>,
+
+    /// The number of frames recorded so far.
+    frames: usize,
+
+    /// The GIF being written, once the first frame is recorded.
+    gif: Option<gif::Encoder<BufWriter<File>>>,
+}
+
+impl Recorder {
+    /// Starts a recording. Directories of PNG files are created if needed.
+    ///
+    /// # Parameters:
+    ///     - path:         A `.gif` file, or a directory for PNG files.
+    ///     - frame_step:   The time between recorded frames.
+    pub fn new(path: PathBuf, frame_step: Duration) -> io::Result<Recorder> {
+        let format = RecordingFormat::for_path(&path);
+        if format == RecordingFormat::PngSequence {
+            fs::create_dir_all(&path)?;
+        }
+        Ok(Recorder {
+            path,
+            format,
+            frame_step,
+            size: None,
+            frames: 0,
+            gif: None,
+        })
+    }
+
+    /// The file or directory the recording is written to.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// The time the world moves on by between recorded frames.
+    pub fn frame_step(&self) -> Duration {
+        self.frame_step
+    }
+
+    /// The number of frames recorded so far.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    /// Adds the picture on a canvas to the recording.
+    pub fn record(&mut self, canvas: &Canvas) -> io::Result<()> {
+        let (width, height) = *self.size.get_or_insert((canvas.width(), canvas.height()));
+        let mut frame = Frame::new(width, height);
+        frame.copy_from(canvas);
+
+        match self.format {
+            RecordingFormat::PngSequence => {
+                frame.save_png(self.path.join(format!("frame_{:05}.png", self.frames)))?;
+            }
+            RecordingFormat::Gif => {
+                if self.gif.is_none() {
+                    let file = BufWriter::new(File::create(&self.path)?);
+                    let mut encoder = gif::Encoder::new(file, width as u16, height as u16, &[])
+                        .map_err(gif_error)?;
+                    encoder.set_repeat(gif::Repeat::Infinite).map_err(gif_error)?;
+                    self.gif = Some(encoder);
+                }
+
+                // Each frame gets its own palette, picked from its colors
+                let mut gif_frame = gif::Frame::from_rgb_speed(width as u16, height as u16, frame.pixels(), 10);
+                gif_frame.delay = (self.frame_step.as_secs_f64() * 100.).round() as u16;
+                self.gif.as_mut().unwrap().write_frame(&gif_frame).map_err(gif_error)?;
+            }
+        }
+        self.frames += 1;
+        Ok(())
+    }
+
+    /// Ends the recording, writing out what is left of it.
+    pub fn finish(self) -> io::Result<()> {
+        if let Some(encoder) = self.gif {
+            encoder.into_inner()?.flush()?;
+        }
+        Ok(())
+    }
+}
+
+/// Converts an error of the GIF encoder.
+fn gif_error(error: gif::EncodingError) -> io::Error {
+    match error {
+        gif::EncodingError::Io(error) => error,
+        error => io::Error::other(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn options_default_without_arguments() {
+        assert_eq!(RecordingOptions::from_args(&[]), Ok(RecordingOptions::default()));
+    }
+
+    #[test]
+    fn options_are_read() {
+        let options = RecordingOptions::from_args(&args(&["--frame-step", "0.02", "--record", "frames"])).unwrap();
+        assert_eq!(options.path, Some(PathBuf::from("frames")));
+        assert_eq!(options.frame_step, Duration::from_millis(20));
+    }
+
+    #[test]
+    fn missing_values_are_reported() {
+        assert_eq!(
+            RecordingOptions::from_args(&args(&["--record"])),
+            Err("Missing value of --record".to_string())
+        );
+        assert_eq!(
+            RecordingOptions::from_args(&args(&["--record", "demo.gif", "--frame-step"])),
+            Err("Missing value of --frame-step".to_string())
+        );
+    }
+
+    #[test]
+    fn frame_steps_have_to_be_positive_numbers() {
+        for step in &["0", "-0.04", "NaN", "inf", "fast"] {
+            assert_eq!(
+                RecordingOptions::from_args(&args(&["--frame-step", step])),
+                Err(format!("Invalid frame step: {}", step))
+            );
+        }
+    }
+
+    #[test]
+    fn unknown_options_are_reported() {
+        assert_eq!(
+            RecordingOptions::from_args(&args(&["--record", "demo.gif", "--fps", "30"])),
+            Err("Unknown option: --fps".to_string())
+        );
+    }
+
+    #[test]
+    fn gif_files_are_recorded_as_gif() {
+        assert_eq!(RecordingFormat::for_path(Path::new("demo.gif")), RecordingFormat::Gif);
+        assert_eq!(RecordingFormat::for_path(Path::new("out/Demo.GIF")), RecordingFormat::Gif);
+    }
+
+    #[test]
+    fn other_paths_are_recorded_as_png_files() {
+        for path in &["frames", "frames/", "demo.png", "gif", "demo.gif/frames", "demo.gifs"] {
+            assert_eq!(RecordingFormat::for_path(Path::new(path)), RecordingFormat::PngSequence, "{}", path);
+        }
+    }
+}
@@ -0,0 +1,174 @@
+use crate::game::Game;
+use crate::hyperbolic_renderer;
+use crate::layout::{Layout, View};
+use crate::poincare_renderer;
+use crate::poincare_renderer::viewport::Viewport;
+use crate::utils::color::RGBColor;
+use crate::window::canvas::Canvas;
+
+/// Draws frames of a game. Renderers keep their own settings; the game they draw is
+/// lent to them for each frame, so several renderers can show the same game.
+pub trait Renderer {
+    /// Renders one frame into a canvas.
+    ///
+    /// # Parameters:
+    ///     - game:         The virtual world state to be drawn.
+    ///     - canvas:       The canvas that should be drawn to.
+    fn render(&self, game: &Game, canvas: &mut Canvas);
+
+    /// Number of rays cast for the last frame, 0 for renderers that do not cast rays.
+    fn rays_cast(&self) -> usize {
+        0
+    }
+
+    /// The top-down view controlled by the zoom, pan and projection keys, if the renderer shows one.
+    fn viewport_mut(&mut self) -> Option<&mut Viewport> {
+        None
+    }
+}
+
+/// The available renderers.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RendererKind {
+    /// The player's view, see `hyperbolic_renderer`.
+    Hyperbolic,
+
+    /// The top-down view of the Poincaré disk, see `poincare_renderer`.
+    Poincare,
+}
+
+impl RendererKind {
+    /// The next renderer, wrapping around.
+    pub fn next(self) -> RendererKind {
+        match self {
+            RendererKind::Hyperbolic => RendererKind::Poincare,
+            RendererKind::Poincare => RendererKind::Hyperbolic,
+        }
+    }
+}
+
+/// One renderer of each kind, with the one in use selected. Settings of the renderers
+/// not in use are kept while switching between them.
+pub struct Renderers {
+    pub hyperbolic: hyperbolic_renderer::Renderer,
+    pub poincare: poincare_renderer::Renderer,
+
+    /// The renderer in use
+    pub kind: RendererKind,
+
+    /// How the window is divided between the renderers
+    pub layout: Layout,
+}
+
+impl Renderers {
+    /// Initializes every renderer, with the player's view in use.
+    ///
+    /// # Parameters:
+    ///     - game:                     The virtual world state, for the settings of its map (e.g. fog).
+    ///     - relative_screen_size:     The size of the physical computer display in relation to a grid field
+    ///     - focal_length:             A focal length that should be used for rendering.
+    ///     - illumination_radius:      The radius around the player where objects should appear illuminated.
+    ///     - minimum_light:            The minimum environment light of the scene.
+    pub fn new(
+        game: &Game,
+        relative_screen_size: f64,
+        focal_length: f64,
+        illumination_radius: f64,
+        minimum_light: f64,
+    ) -> Renderers {
+        Renderers {
+            hyperbolic: hyperbolic_renderer::Renderer::new(
+                game,
+                relative_screen_size,
+                focal_length,
+                illumination_radius,
+                minimum_light,
+            ),
+            poincare: poincare_renderer::Renderer::new(
+                relative_screen_size,
+                focal_length,
+                illumination_radius,
+                minimum_light,
+            ),
+            kind: RendererKind::Hyperbolic,
+            layout: Layout::Single,
+        }
+    }
+
+    /// The renderer in use.
+    pub fn active(&self) -> &dyn Renderer {
+        match self.kind {
+            RendererKind::Hyperbolic => &self.hyperbolic,
+            RendererKind::Poincare => &self.poincare,
+        }
+    }
+
+    /// The renderer in use, for changing its settings.
+    pub fn active_mut(&mut self) -> &mut dyn Renderer {
+        match self.kind {
+            RendererKind::Hyperbolic => &mut self.hyperbolic,
+            RendererKind::Poincare => &mut self.poincare,
+        }
+    }
+
+    /// Renders one frame into a canvas, each pane of the layout with its view.
+    ///
+    /// # Parameters:
+    ///     - game:         The virtual world state to be drawn.
+    ///     - canvas:       The canvas that should be drawn to.
+    pub fn render(&self, game: &Game, canvas: &mut Canvas) {
+        let panes = self.layout.panes(canvas.width(), canvas.height());
+        if panes.len() > 1 {
+            canvas.fill(&RGBColor::dark_gray());
+        }
+
+        for pane in panes {
+            let mut pane_canvas = canvas.sub_canvas(pane.x, pane.y, pane.width, pane.height);
+            match pane.view {
+                View::Active => self.active().render(game, &mut pane_canvas),
+                View::FirstPerson => self.hyperbolic.render(game, &mut pane_canvas),
+                View::TopDown(projection) => {
+                    let mut viewport = self.poincare.viewport.clone();
+                    if let Some(projection) = projection {
+                        viewport.projection = projection;
+                    }
+
+                    let (left_angle, right_angle) = self.hyperbolic.view_cone();
+                    pane_canvas.fill(&RGBColor::black());
+                    poincare_renderer::Renderer::draw_map(game, &viewport, &mut pane_canvas);
+                    poincare_renderer::Renderer::draw_view_cone(left_angle, right_angle, &viewport, &mut pane_canvas);
+
+                    // The rays of this frame, if the first-person view was drawn
+                    if self.rays_cast() > 0 {
+                        poincare_renderer::Renderer::draw_rays(&self.hyperbolic.debug_rays(), &viewport, &mut pane_canvas);
+                    }
+
+                    // Name the model in the bottom left corner
+                    let scale = (pane.height / 240).max(1);
+                    if pane.height > Canvas::text_height(scale) + 4 * scale {
+                        let y = pane.height - Canvas::text_height(scale) - 2 * scale;
+                        pane_canvas.draw_text(2 * scale, y, viewport.projection.name(), &RGBColor::white(), scale);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Number of rays cast for the last frame, 0 if no first-person view was drawn.
+    pub fn rays_cast(&self) -> usize {
+        match self.layout {
+            Layout::Single => self.active().rays_cast(),
+            Layout::SideBySide => self.hyperbolic.rays_cast(),
+            Layout::Models => 0,
+        }
+    }
+
+    /// The top-down view controlled by the zoom, pan and projection keys: the one of the renderer
+    /// in use, or the one shared by the top-down panes of a split layout.
+    pub fn viewport_mut(&mut self) -> Option<&mut Viewport> {
+        match self.layout {
+            Layout::Single => self.active_mut().viewport_mut(),
+            Layout::SideBySide | Layout::Models => Some(&mut self.poincare.viewport),
+        }
+    }
+}
@@ -1,6 +1,10 @@
+use std::convert::TryFrom;
+use std::ops::{Add, AddAssign, Mul};
+
 use serde::Deserialize;
 /// Represents a RGB color
-#[derive(PartialEq, Eq, Clone, Debug, Deserialize)]
+#[derive(PartialEq, Eq, Clone, Debug, Default, Deserialize)]
+#[serde(try_from = "ColorDefinition")]
 pub struct RGBColor {
     /// The red component of a color
     pub red: u8,
@@ -12,7 +16,58 @@ pub struct RGBColor {
     pub blue: u8,
 }
 
+/// Color as written in the map file: either its components, or a hex string like `"#ff8000"` or `"#f80"`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorDefinition {
+    Components { red: u8, green: u8, blue: u8 },
+    Hex(String),
+}
+
+impl TryFrom<ColorDefinition> for RGBColor {
+    type Error = String;
+
+    fn try_from(definition: ColorDefinition) -> Result<Self, Self::Error> {
+        match definition {
+            ColorDefinition::Components { red, green, blue } => Ok(RGBColor { red, green, blue }),
+            ColorDefinition::Hex(hex) => RGBColor::from_hex(&hex),
+        }
+    }
+}
+
 impl RGBColor {
+    /// Parses a hex color string, `#rrggbb` or the short `#rgb`. The `#` is optional.
+    pub fn from_hex(hex: &str) -> Result<RGBColor, String> {
+        let digits = hex.trim_start_matches('#');
+        let invalid = || format!("Invalid hex color: {}", hex);
+        if !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+
+        let component = |digits: &str| u8::from_str_radix(digits, 16).map_err(|_| invalid());
+        match digits.len() {
+            6 => Ok(RGBColor {
+                red: component(&digits[0..2])?,
+                green: component(&digits[2..4])?,
+                blue: component(&digits[4..6])?,
+            }),
+            // Each digit is doubled, #f80 is #ff8800
+            3 => Ok(RGBColor {
+                red: component(&digits[0..1])? * 17,
+                green: component(&digits[1..2])? * 17,
+                blue: component(&digits[2..3])? * 17,
+            }),
+            _ => Err(invalid()),
+        }
+    }
+
+    pub fn white() -> RGBColor {
+        RGBColor {
+            red: 255,
+            green: 255,
+            blue: 255,
+        }
+    }
     pub fn black() -> RGBColor {
         RGBColor {
             red: 0,
@@ -64,18 +119,343 @@ impl RGBColor {
     }
 }
 
+/// Operations on sRGB colors are done in linear light, see `LinearColor`.
 impl RGBColor {
-    /// Creates a new color by darkening the color to the given percentage.
+    /// Creates a new color by darkening the color to the given percentage of its light.
     pub fn adjust_light_intensity(&self, percentage: f64) -> RGBColor {
+        (LinearColor::from(self) * percentage.clamp(0.0, 1.0)).to_srgb()
+    }
+
+    /// Creates a new color lit by the given light. Unlike `adjust_light_intensity`
+    /// this can also brighten the color, up to the maximum value of each component.
+    pub fn illuminate(&self, illumination: &Illumination) -> RGBColor {
+        LinearColor::from(self).illuminate(illumination).to_srgb()
+    }
+
+    /// Creates a new color as seen through a colored filter (e.g. reflected by a tinted mirror).
+    pub fn tint(&self, filter: &RGBColor) -> RGBColor {
+        LinearColor::from(self).tint(&filter.into()).to_srgb()
+    }
+
+    /// Creates a new color blended towards another one, by `amount` (0: this color, 1: the other one).
+    pub fn mix(&self, other: &RGBColor, amount: f64) -> RGBColor {
+        LinearColor::from(self).mix(&other.into(), amount).to_srgb()
+    }
+}
+
+/// A color in linear light, where light adds up and scales the way physical light does,
+/// unlike the gamma encoded sRGB components of `RGBColor`.
+/// Full sRGB intensity is 1.0, brighter light goes above it until it is tone mapped.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct LinearColor {
+    pub red: f64,
+    pub green: f64,
+    pub blue: f64,
+}
+
+/// How much light beyond full intensity turns an overexposed color about two thirds of the way
+/// to white, see `LinearColor::tone_map`.
+const OVEREXPOSURE_TO_WHITE: f64 = 1.0;
+
+/// Ordered dithering thresholds, in sixteenths of a step between two 8-bit values.
+const BAYER_MATRIX: [[u8; 4]; 4] = [[0, 8, 2, 10], [12, 4, 14, 6], [3, 11, 1, 9], [15, 7, 13, 5]];
+
+impl LinearColor {
+    pub fn black() -> LinearColor {
+        LinearColor::default()
+    }
+
+    pub fn white() -> LinearColor {
+        LinearColor {
+            red: 1.0,
+            green: 1.0,
+            blue: 1.0,
+        }
+    }
+
+    /// Creates a new color lit by the given light.
+    pub fn illuminate(&self, illumination: &Illumination) -> LinearColor {
+        LinearColor {
+            red: self.red * illumination.red,
+            green: self.green * illumination.green,
+            blue: self.blue * illumination.blue,
+        }
+    }
+
+    /// Creates a new color as seen through a colored filter.
+    pub fn tint(&self, filter: &LinearColor) -> LinearColor {
+        LinearColor {
+            red: self.red * filter.red,
+            green: self.green * filter.green,
+            blue: self.blue * filter.blue,
+        }
+    }
+
+    /// Creates a new color blended towards another one, by `amount` (0: this color, 1: the other one).
+    pub fn mix(&self, other: &LinearColor, amount: f64) -> LinearColor {
+        let amount = amount.clamp(0.0, 1.0);
+        *self * (1.0 - amount) + *other * amount
+    }
+
+    /// Compresses light brighter than full intensity smoothly into the displayable range, so
+    /// overexposed colors keep some detail instead of being cut off at full intensity.
+    /// The color is dimmed until its brightest component is at full intensity, keeping its hue,
+    /// and the light beyond that turns it towards white. Light within the range is kept as it is.
+    pub fn tone_map(&self) -> LinearColor {
+        let brightest = self.red.max(self.green).max(self.blue);
+        if brightest <= 1.0 {
+            return *self;
+        }
+
+        let whiteness = 1.0 - (-(brightest - 1.0) / OVEREXPOSURE_TO_WHITE).exp();
+        let map_component = |component: f64| {
+            let dimmed = component / brightest;
+            dimmed + (1.0 - dimmed) * whiteness
+        };
+        LinearColor {
+            red: map_component(self.red),
+            green: map_component(self.green),
+            blue: map_component(self.blue),
+        }
+    }
+
+    /// Encodes the color to sRGB, cutting off light brighter than full intensity.
+    pub fn to_srgb(self) -> RGBColor {
+        self.encode(0.5)
+    }
+
+    /// Encodes the color to sRGB, rounding up or down depending on the pixel's place in an ordered
+    /// dithering pattern. Smooth gradients then don't show bands of the 8-bit components.
+    pub fn to_srgb_dithered(self, x: usize, y: usize) -> RGBColor {
+        self.encode((BAYER_MATRIX[y % 4][x % 4] as f64 + 0.5) / 16.0)
+    }
+
+    /// Encodes the color to sRGB, rounding up components with a fraction above `1 - threshold`.
+    fn encode(&self, threshold: f64) -> RGBColor {
+        let encode_component = |component: f64| {
+            let encoded = match component <= 0.0031308 {
+                true => 12.92 * component,
+                false => 1.055 * component.powf(1.0 / 2.4) - 0.055,
+            };
+            (encoded * 255.0 + threshold).floor().clamp(0.0, 255.0) as u8
+        };
         RGBColor {
-            red: RGBColor::darken_component(self.red, percentage),
-            green: RGBColor::darken_component(self.green, percentage),
-            blue: RGBColor::darken_component(self.blue, percentage),
+            red: encode_component(self.red),
+            green: encode_component(self.green),
+            blue: encode_component(self.blue),
+        }
+    }
+}
+
+impl From<&RGBColor> for LinearColor {
+    /// Decodes the gamma encoded sRGB components.
+    fn from(color: &RGBColor) -> LinearColor {
+        let decode_component = |component: u8| {
+            let encoded = component as f64 / 255.0;
+            match encoded <= 0.04045 {
+                true => encoded / 12.92,
+                false => ((encoded + 0.055) / 1.055).powf(2.4),
+            }
+        };
+        LinearColor {
+            red: decode_component(color.red),
+            green: decode_component(color.green),
+            blue: decode_component(color.blue),
+        }
+    }
+}
+
+impl Add for LinearColor {
+    type Output = LinearColor;
+
+    /// Light of both colors together.
+    fn add(self, other: LinearColor) -> LinearColor {
+        LinearColor {
+            red: self.red + other.red,
+            green: self.green + other.green,
+            blue: self.blue + other.blue,
+        }
+    }
+}
+
+impl AddAssign for LinearColor {
+    fn add_assign(&mut self, other: LinearColor) {
+        *self = *self + other;
+    }
+}
+
+impl Mul<f64> for LinearColor {
+    type Output = LinearColor;
+
+    /// The color with its light scaled by a factor.
+    fn mul(self, factor: f64) -> LinearColor {
+        LinearColor {
+            red: self.red * factor,
+            green: self.green * factor,
+            blue: self.blue * factor,
+        }
+    }
+}
+
+/// Amount of light reaching a surface, per color component.
+/// 1.0 leaves the surface color unchanged.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Illumination {
+    pub red: f64,
+    pub green: f64,
+    pub blue: f64,
+}
+
+impl Illumination {
+    /// White light of the given intensity.
+    pub fn white(intensity: f64) -> Illumination {
+        Illumination {
+            red: intensity,
+            green: intensity,
+            blue: intensity,
+        }
+    }
+
+    /// Adds a colored light of the given brightness.
+    pub fn add_light(&mut self, color: &RGBColor, brightness: f64) {
+        let color = LinearColor::from(color);
+        self.red += brightness * color.red;
+        self.green += brightness * color.green;
+        self.blue += brightness * color.blue;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn srgb_round_trips_through_linear_light() {
+        for value in 0..=255 {
+            let color = RGBColor {
+                red: value,
+                green: 255 - value,
+                blue: value / 2,
+            };
+            assert_eq!(LinearColor::from(&color).to_srgb(), color);
+        }
+    }
+
+    /// Dithering only rounds the other way, never by more than one step.
+    #[test]
+    fn dithered_srgb_stays_within_a_step() {
+        for value in 0..=255u8 {
+            let linear = LinearColor::from(&RGBColor {
+                red: value,
+                green: value,
+                blue: value,
+            });
+            for (x, y) in (0..4).flat_map(|x| (0..4).map(move |y| (x, y))) {
+                let dithered = linear.to_srgb_dithered(x, y);
+                assert!((dithered.red as i16 - value as i16).abs() <= 1, "{} becomes {:?}", value, dithered);
+            }
+        }
+    }
+
+    /// Light within the displayable range is shown as it is, full intensity included.
+    #[test]
+    fn tone_mapping_keeps_light_within_range() {
+        assert_eq!(LinearColor::white().tone_map().to_srgb(), RGBColor::white());
+        for value in 0..=255 {
+            let color = RGBColor {
+                red: value,
+                green: 255 - value,
+                blue: value / 2,
+            };
+            assert_eq!(LinearColor::from(&color).tone_map().to_srgb(), color);
+        }
+    }
+
+    /// Overexposed light stays in range, keeps its brightest component at full intensity and gets whiter.
+    #[test]
+    fn tone_mapping_turns_overexposed_light_white() {
+        let light = |intensity: f64| {
+            LinearColor {
+                red: intensity,
+                green: intensity / 2.,
+                blue: 0.,
+            }
+            .tone_map()
+        };
+        let mut previous = light(1.);
+        for intensity in [1.5, 2., 4., 8.] {
+            let mapped = light(intensity);
+            assert_eq!(mapped.red, 1.);
+            assert!(mapped.green > previous.green && mapped.green < 1., "{:?}", mapped);
+            assert!(mapped.blue > previous.blue && mapped.blue < 1., "{:?}", mapped);
+            previous = mapped;
+        }
+    }
+
+    #[test]
+    fn srgb_decodes_to_linear_light() {
+        assert_eq!(LinearColor::from(&RGBColor::black()), LinearColor::black());
+        assert_eq!(LinearColor::from(&RGBColor::white()), LinearColor::white());
+
+        // Mid gray in sRGB is about a fifth of the light of white
+        let gray = LinearColor::from(&RGBColor {
+            red: 128,
+            green: 128,
+            blue: 128,
+        });
+        assert!((gray.red - 0.2158605).abs() < 1e-6, "{}", gray.red);
+    }
+
+    #[test]
+    fn light_above_full_intensity_is_cut_off() {
+        let bright = LinearColor {
+            red: 2.0,
+            green: -1.0,
+            blue: 1.0,
+        };
+        assert_eq!(
+            bright.to_srgb(),
+            RGBColor {
+                red: 255,
+                green: 0,
+                blue: 255
+            }
+        );
+    }
+
+    #[test]
+    fn hex_colors_are_parsed() {
+        let orange = RGBColor {
+            red: 255,
+            green: 128,
+            blue: 0,
+        };
+        assert_eq!(RGBColor::from_hex("#ff8000"), Ok(orange.clone()));
+        assert_eq!(RGBColor::from_hex("FF8000"), Ok(orange));
+
+        // Short colors double each digit
+        assert_eq!(
+            RGBColor::from_hex("#f80"),
+            Ok(RGBColor {
+                red: 255,
+                green: 136,
+                blue: 0
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_hex_colors_are_rejected() {
+        for hex in &["", "#", "#ff80", "#ff80001", "#gg8000", "#+f+f+f", "ff 800"] {
+            assert!(RGBColor::from_hex(hex).is_err(), "{} is accepted", hex);
         }
     }
 
-    /// Determines the value of a certain color component when darkening a color.
-    fn darken_component(component: u8, percentage: f64) -> u8 {
-        (((component as f64) * percentage).max(0.0) as u8).min(component)
+    #[test]
+    fn colors_are_read_as_components_or_hex() {
+        let components: RGBColor = serde_json::from_str(r#"{"red": 255, "green": 128, "blue": 0}"#).unwrap();
+        let hex: RGBColor = serde_json::from_str(r##""#ff8000""##).unwrap();
+        assert_eq!(components, hex);
+        assert!(serde_json::from_str::<RGBColor>(r#""orange""#).is_err());
     }
 }
@@ -1,8 +1,10 @@
 use std::cmp;
+use std::rc::Rc;
 
-use super::color::RGBColor;
+use super::color::{Illumination, RGBColor};
 use crate::utils::*;
 use cmp::Ordering;
+use material::Material;
 use nalgebra::*;
 use point::{Point, Wall};
 use poincarepoint::{PoincarePoint, PoincareWall};
@@ -18,6 +20,13 @@ impl From<PoincarePoint> for Hyperpoint {
     fn from(poincare_point: PoincarePoint) -> Self {
         //Minkowski metric
         let norm_squared = PoincarePoint::minkowski_dot(&poincare_point, &poincare_point);
+
+        // Points on the boundary of the disk (or past it) are at infinity
+        if norm_squared >= 1.0 {
+            let norm = norm_squared.sqrt();
+            return Hyperpoint::new_ideal(poincare_point.0[0] / norm, poincare_point.0[1] / norm);
+        }
+
         Hyperpoint::new_with_z(
             (poincare_point.0[0] * 2.0) / (1.0 - norm_squared),
             (poincare_point.0[1] * 2.0) / (1.0 - norm_squared),
@@ -45,13 +54,32 @@ impl Hyperpoint {
         }
     }
 
+    /// Constructs an ideal point: the point at infinity in the direction of the unit vector (x, y).
+    /// It is represented by a vector on the light cone z^2 = x^2 + y^2, the asymptote of the
+    /// hyperboloid. Rotations and translations map the light cone onto itself, so ideal
+    /// points move along with the rest of the map.
+    pub fn new_ideal(x: f64, y: f64) -> Hyperpoint {
+        Hyperpoint::new_with_z(x, y, 1.0)
+    }
+
+    /// Whether the point is an ideal point, on the light cone rather than on the hyperboloid.
+    pub fn is_ideal(&self) -> bool {
+        // z^2 - x^2 - y^2 is 1 on the hyperboloid and 0 on the light cone
+        self.0[2].powi(2) - self.0[0].powi(2) - self.0[1].powi(2) < 0.5
+    }
+
     /// Rotates the point around the z axis at origin. Ordinary rotation.
     pub fn rotate(&mut self, angle: f64) {
+        self.0 = Hyperpoint::rotation_matrix(angle) * self.0;
+    }
+
+    /// The matrix of `rotate`.
+    pub fn rotation_matrix(angle: f64) -> Matrix3<f64> {
         let rot = Rotation3::from_axis_angle(
             &Unit::new_normalize(Vector3::<f64>::new(0.0, 0.0, 1.0)),
             angle,
         );
-        self.0 = rot.transform_point(&self.0);
+        *rot.matrix()
     }
 
     /// Performs the equivalent of translation in the hyperboloid model,
@@ -59,6 +87,16 @@ impl Hyperpoint {
     /// See the following for the explanation:
     /// https://math.stackexchange.com/questions/1862340/what-are-the-hyperbolic-rotation-matrices-in-3-and-4-dimensions?newreg=0a895728ef9c48ad814e2f06eafb3862
     pub fn translate(&mut self, x: f64, y: f64) {
+        self.0 = Hyperpoint::translation_matrix(x, y) * self.0;
+
+        // Only the direction of an ideal point matters, keep its length from growing or shrinking
+        if self.is_ideal() {
+            self.0 = Point3::<f64>::new(self.0[0] / self.0[2], self.0[1] / self.0[2], 1.0);
+        }
+    }
+
+    /// The matrix of `translate`.
+    pub fn translation_matrix(x: f64, y: f64) -> Matrix3<f64> {
         let coshb = f64::cosh(x);
         let sinhb = f64::sinh(x);
         let coshy = f64::cosh(-y);
@@ -66,8 +104,21 @@ impl Hyperpoint {
         let translation1 = Matrix3::new(coshb, 0., sinhb, 0., 1., 0., sinhb, 0., coshb);
         let translation2 = Matrix3::new(1., 0., 0., 0., coshy, sinhy, 0., sinhy, coshy);
 
-        let translation = translation1 * translation2;
-        self.0 = translation * &self.0;
+        translation1 * translation2
+    }
+
+    /// Returns the point on the geodesic segment from this point to `to`,
+    /// at the fraction `t` (0…1) of its length.
+    pub fn interpolate(&self, to: &Hyperpoint, t: f64) -> Hyperpoint {
+        let distance = self.distance_to(to);
+        if distance < 1e-12 {
+            return self.clone();
+        }
+        let weight_from = ((1. - t) * distance).sinh() / distance.sinh();
+        let weight_to = (t * distance).sinh() / distance.sinh();
+        Hyperpoint(Point3::from(
+            self.0.coords * weight_from + to.0.coords * weight_to,
+        ))
     }
 }
 
@@ -78,8 +129,11 @@ impl point::Point for Hyperpoint {
         a.0[0] * b.0[0] + a.0[1] * b.0[1] - a.0[2] * b.0[2]
     }
 
-    /// Distance to origin in the Minkowski hyperboloid metric.
+    /// Distance to origin in the Minkowski hyperboloid metric, infinite for ideal points.
     fn distance_to_origin(&self) -> f64 {
+        if self.is_ideal() {
+            return f64::INFINITY;
+        }
         let minkowski_bilinear: f64 = self.0[2];
         minkowski_bilinear.acosh()
     }
@@ -89,8 +143,11 @@ impl point::Point for Hyperpoint {
         Hyperpoint::new_with_z(0., 0., 1.)
     }
 
-    /// Distance to another point in the Minkowski hyperboloid metric.
+    /// Distance to another point in the Minkowski hyperboloid metric, infinite for ideal points.
     fn distance_to(&self, to: &Self) -> f64 {
+        if self.is_ideal() || to.is_ideal() {
+            return f64::INFINITY;
+        }
         let minkowski_bilinear: f64 =
             self.0[2] * to.0[2] - self.0[1] * to.0[1] - self.0[0] * to.0[0];
         minkowski_bilinear.acosh()
@@ -102,6 +159,28 @@ pub struct HyperWall {
     pub beginning: Hyperpoint,
     pub end: Hyperpoint,
     pub color: RGBColor,
+
+    /// The look of the wall's sides, if it has a material.
+    #[serde(skip)]
+    pub material: Option<Rc<Material>>,
+
+    /// Whether the wall is a mirror.
+    #[serde(default)]
+    pub reflective: bool,
+
+    /// Light of the map lights at evenly spaced points from the beginning
+    /// to the end of the wall, if precomputed.
+    /// Walls and lights move together, so it stays valid as the player moves.
+    #[serde(skip)]
+    pub lightmap: Option<Rc<Vec<Illumination>>>,
+
+    /// Geodesic curvature of the wall, see `PoincareWall`.
+    #[serde(default)]
+    pub curvature: f64,
+
+    /// The middle point of a curved wall, moved along with its ends.
+    #[serde(skip)]
+    pub through: Option<Hyperpoint>,
 }
 
 impl HyperWall {
@@ -140,10 +219,21 @@ impl Wall for HyperWall {
 
 impl From<PoincareWall> for HyperWall {
     fn from(poincare_wall: PoincareWall) -> HyperWall {
+        let through = match (&poincare_wall.through, poincare_wall.curvature) {
+            (Some(through), _) => Some(through.clone()),
+            (None, curvature) if curvature != 0. => Some(poincare_wall.find_curve_midpoint(curvature)),
+            (None, _) => None,
+        };
+
         HyperWall {
             beginning: poincare_wall.beginning.into(),
             end: poincare_wall.end.into(),
             color: poincare_wall.color,
+            material: poincare_wall.material,
+            reflective: poincare_wall.reflective,
+            lightmap: poincare_wall.lightmap,
+            curvature: poincare_wall.curvature,
+            through: through.map(|through| through.into()),
         }
     }
 }
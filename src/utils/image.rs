@@ -0,0 +1,99 @@
+use std::collections::HashMap;
+
+use serde::Deserialize;
+
+use super::color::RGBColor;
+
+/// A small paletted picture, e.g. the look of a sprite.
+/// Pixels without a color are transparent.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(from = "ImageDefinition")]
+pub struct Image {
+    width: usize,
+    height: usize,
+    pixels: Vec<Option<RGBColor>>,
+}
+
+/// Image as written in the map file: every character of a row
+/// is a pixel, looked up in the palette. Characters missing from
+/// the palette (e.g. `.`) are transparent.
+#[derive(Deserialize)]
+struct ImageDefinition {
+    palette: HashMap<char, RGBColor>,
+    rows: Vec<String>,
+}
+
+impl From<ImageDefinition> for Image {
+    fn from(definition: ImageDefinition) -> Self {
+        let height = definition.rows.len();
+        let width = definition
+            .rows
+            .iter()
+            .map(|row| row.chars().count())
+            .max()
+            .unwrap_or(0);
+
+        let mut pixels = Vec::with_capacity(width * height);
+        for row in &definition.rows {
+            let mut chars = row.chars();
+            for _ in 0..width {
+                let pixel = chars
+                    .next()
+                    .and_then(|c| definition.palette.get(&c))
+                    .cloned();
+                pixels.push(pixel);
+            }
+        }
+
+        Image {
+            width,
+            height,
+            pixels,
+        }
+    }
+}
+
+impl Image {
+    /// The width of the image in pixels.
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    /// The height of the image in pixels.
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Color of the pixel at the given coordinates, `None` if transparent.
+    pub fn pixel(&self, x: usize, y: usize) -> Option<&RGBColor> {
+        self.pixels[y * self.width + x].as_ref()
+    }
+
+    /// The mean color of the opaque pixels, black if there are none.
+    pub fn average_color(&self) -> RGBColor {
+        let opaque: Vec<&RGBColor> = self.pixels.iter().flatten().collect();
+        if opaque.is_empty() {
+            return RGBColor::black();
+        }
+
+        let mean = |component: fn(&RGBColor) -> u8| {
+            let sum: f64 = opaque.iter().map(|color| component(color) as f64).sum();
+            (sum / opaque.len() as f64).round() as u8
+        };
+        RGBColor {
+            red: mean(|color| color.red),
+            green: mean(|color| color.green),
+            blue: mean(|color| color.blue),
+        }
+    }
+
+    /// Color of the pixel at relative coordinates, both in 0…1.
+    pub fn sample(&self, u: f64, v: f64) -> Option<&RGBColor> {
+        if self.pixels.is_empty() {
+            return None;
+        }
+        let x = ((u * self.width as f64) as usize).min(self.width - 1);
+        let y = ((v * self.height as f64) as usize).min(self.height - 1);
+        self.pixel(x, y)
+    }
+}
@@ -0,0 +1,132 @@
+use std::{collections::HashMap, rc::Rc};
+
+use serde::Deserialize;
+
+use super::{color::RGBColor, image::Image};
+
+/// The look of a wall, shared by all walls using it.
+#[derive(Clone, Debug)]
+pub struct Material {
+    /// The side on the left of the way from the wall's beginning to its end.
+    pub front: Surface,
+
+    /// The other side.
+    pub back: Surface,
+
+    /// Whether the wall glows by itself, ignoring the lights.
+    pub emissive: bool,
+
+    /// Whether the wall is a mirror.
+    pub reflective: bool,
+}
+
+impl Material {
+    /// The surface on the given side of the wall.
+    pub fn side(&self, front: bool) -> &Surface {
+        match front {
+            true => &self.front,
+            false => &self.back,
+        }
+    }
+}
+
+/// The colors of one side of a wall.
+#[derive(Clone, Debug)]
+pub enum Surface {
+    /// The same color everywhere.
+    Color(RGBColor),
+
+    /// Blends from the color at the top of the wall to the color at its bottom.
+    Gradient { top: RGBColor, bottom: RGBColor },
+
+    /// An image repeated along the wall, each copy as wide as the wall is high.
+    /// Transparent pixels are black.
+    Texture(Rc<Image>),
+}
+
+impl Surface {
+    /// Color of a point of the surface.
+    ///
+    /// # Parameters:
+    ///     - u:    Distance along the wall from its beginning, in wall heights.
+    ///     - v:    Height on the wall, 0 at the top and 1 at the bottom.
+    pub fn color_at(&self, u: f64, v: f64) -> RGBColor {
+        match self {
+            Surface::Color(color) => color.clone(),
+            Surface::Gradient { top, bottom } => top.mix(bottom, v),
+            Surface::Texture(image) => image
+                .sample(u.rem_euclid(1.), v.clamp(0., 1.))
+                .cloned()
+                .unwrap_or_else(RGBColor::black),
+        }
+    }
+
+    /// A single color standing for the whole surface, e.g. on the top-down view.
+    pub fn base_color(&self) -> RGBColor {
+        match self {
+            Surface::Color(color) => color.clone(),
+            Surface::Gradient { top, bottom } => top.mix(bottom, 0.5),
+            Surface::Texture(image) => image.average_color(),
+        }
+    }
+}
+
+/// Material as written in the map file.
+#[derive(Debug, Deserialize)]
+pub struct MaterialDefinition {
+    pub front: SurfaceDefinition,
+
+    /// The same as the front if missing.
+    #[serde(default)]
+    pub back: Option<SurfaceDefinition>,
+
+    #[serde(default)]
+    pub emissive: bool,
+
+    #[serde(default)]
+    pub reflective: bool,
+}
+
+/// Surface as written in the map file: a color, `{"top": …, "bottom": …}`
+/// or `{"texture": "<image name>"}`.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum SurfaceDefinition {
+    Color(RGBColor),
+    Gradient { top: RGBColor, bottom: RGBColor },
+    Texture { texture: String },
+}
+
+impl MaterialDefinition {
+    /// Creates the material, looking textures up in the map's image library.
+    /// Returns an error naming the texture if it is not in the library.
+    pub fn resolve(self, images: &HashMap<String, Rc<Image>>) -> Result<Material, String> {
+        let front = self.front.resolve(images)?;
+        let back = match self.back {
+            Some(back) => back.resolve(images)?,
+            None => front.clone(),
+        };
+
+        Ok(Material {
+            front,
+            back,
+            emissive: self.emissive,
+            reflective: self.reflective,
+        })
+    }
+}
+
+impl SurfaceDefinition {
+    fn resolve(self, images: &HashMap<String, Rc<Image>>) -> Result<Surface, String> {
+        Ok(match self {
+            SurfaceDefinition::Color(color) => Surface::Color(color),
+            SurfaceDefinition::Gradient { top, bottom } => Surface::Gradient { top, bottom },
+            SurfaceDefinition::Texture { texture } => Surface::Texture(
+                images
+                    .get(&texture)
+                    .ok_or_else(|| format!("Unknown texture image: {}", texture))?
+                    .clone(),
+            ),
+        })
+    }
+}
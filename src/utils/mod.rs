@@ -1,4 +1,6 @@
 pub mod color;
 pub mod hyperpoint;
+pub mod image;
+pub mod material;
 pub mod point;
 pub mod poincarepoint;
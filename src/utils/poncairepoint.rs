@@ -1,4 +1,5 @@
 use std::cmp::Ordering;
+use std::rc::Rc;
 
 use hyperpoint::{HyperWall, Hyperpoint};
 use nalgebra::*;
@@ -7,7 +8,11 @@ use serde::Deserialize;
 
 use crate::utils::hyperpoint;
 
-use super::{color::RGBColor, point};
+use super::{
+    color::{Illumination, RGBColor},
+    material::Material,
+    point,
+};
 
 /// Struct representing a point on the 
 /// Poincare disk model.
@@ -17,7 +22,11 @@ pub struct PoincarePoint(pub Point2<f64>);
 
 impl From<Hyperpoint> for PoincarePoint {
     fn from(hyperpoint: Hyperpoint) -> Self {
-        let denom = hyperpoint.0[2] + 1.0;
+        // Ideal points end up on the boundary of the disk
+        let denom = match hyperpoint.is_ideal() {
+            true => hyperpoint.0[2],
+            false => hyperpoint.0[2] + 1.0,
+        };
         PoincarePoint::new(hyperpoint.0[0] / denom, hyperpoint.0[1] / denom)
     }
 }
@@ -28,13 +37,37 @@ impl PoincarePoint {
             0: Point2::<f64>::new(x, y),
         }
     }
+
+    /// Whether the point is on the boundary of the disk, infinitely far away.
+    pub fn is_ideal(&self) -> bool {
+        PoincarePoint::minkowski_dot(self, self) >= 1. - 1e-9
+    }
+
+    /// Rotates the point around the center of the disk.
+    pub fn rotate(&self, angle: f64) -> PoincarePoint {
+        let (sin, cos) = angle.sin_cos();
+        PoincarePoint::new(self.0[0] * cos - self.0[1] * sin, self.0[0] * sin + self.0[1] * cos)
+    }
+
+    /// Applies the isometry which moves `origin` to the center of the disk,
+    /// followed by a rotation by `-angle` around the center.
+    /// This is the Mobius transformation z -> e^(-i*angle) * (z - a) / (1 - conj(a) * z).
+    pub fn transform_to_origin(&self, origin: &PoincarePoint, angle: f64) -> PoincarePoint {
+        let z = Complex::new(self.0[0], self.0[1]);
+        let a = Complex::new(origin.0[0], origin.0[1]);
+        let one = Complex::new(1., 0.);
+        let rotation = Complex::new(angle.cos(), -angle.sin());
+
+        let result = rotation * (z - a) / (one - a.conj() * z);
+        PoincarePoint::new(result.re, result.im)
+    }
 }
 
 impl point::Point for PoincarePoint {
-    /// Return the Minkowski inner product of the two vectors provided, where the
-    /// last co-ordinate is interpreted as being time-like.
+    /// The disk has no time-like co-ordinate, so this is the ordinary dot product
+    /// of the two vectors, e.g. the squared Euclidean distance from the center for `a == b`.
     fn minkowski_dot(a: &PoincarePoint, b: &PoincarePoint) -> f64 {
-        a.0[0] * b.0[0] - a.0[1] * b.0[1]
+        a.0[0] * b.0[0] + a.0[1] * b.0[1]
     }
 
     /// Distance to origin in the Poincare metric.
@@ -76,7 +109,37 @@ impl point::Point for PoincarePoint {
 pub struct PoincareWall {
     pub beginning: PoincarePoint,
     pub end: PoincarePoint,
+
+    /// Color of a wall without a material. With a material, the color standing for it.
+    #[serde(default)]
     pub color: RGBColor,
+
+    /// Name of the wall's material in the map's material library.
+    #[serde(default, rename = "material")]
+    pub material_name: Option<String>,
+
+    /// The look of the wall's sides, found by `material_name` when the map is loaded.
+    #[serde(skip)]
+    pub material: Option<Rc<Material>>,
+
+    /// Whether the wall is a mirror.
+    #[serde(default)]
+    pub reflective: bool,
+
+    /// Precomputed light of the map lights along the wall, see `HyperWall`.
+    #[serde(skip)]
+    pub lightmap: Option<Rc<Vec<Illumination>>>,
+
+    /// Geodesic curvature of the wall, turning left on the way from the beginning to the end if positive.
+    /// 0: a geodesic segment, below 1: an arc of a hypercycle (tanh of its distance from its axis),
+    /// 1: an arc of a horocycle, above 1: an arc of a circle (coth of its radius).
+    #[serde(default)]
+    pub curvature: f64,
+
+    /// The middle point of a curved wall. Together with the ends it determines the wall's circle
+    /// on the Poincare disk. Found from `curvature` when the map is loaded, see `find_curve_midpoint`.
+    #[serde(skip)]
+    pub through: Option<PoincarePoint>,
 }
 
 impl From<HyperWall> for PoincareWall {
@@ -85,14 +148,70 @@ impl From<HyperWall> for PoincareWall {
             beginning: hyperwall.beginning.into(),
             end: hyperwall.end.into(),
             color: hyperwall.color,
+            material_name: None,
+            material: hyperwall.material,
+            reflective: hyperwall.reflective,
+            lightmap: hyperwall.lightmap,
+            curvature: hyperwall.curvature,
+            through: hyperwall.through.map(|through| through.into()),
         }
     }
 }
 
+/// A full hyperbolic circle as written in the map file, built out of two curved walls.
+#[derive(Clone, Debug, Deserialize)]
+pub struct PoincareCircle {
+    pub center: PoincarePoint,
+
+    /// The hyperbolic radius.
+    pub radius: f64,
+
+    #[serde(default)]
+    pub color: RGBColor,
+
+    /// Name of the circle's material in the map's material library.
+    #[serde(default)]
+    pub material: Option<String>,
+}
+
+impl PoincareCircle {
+    /// Splits the circle into two half circle walls.
+    pub fn into_walls(self) -> Vec<PoincareWall> {
+        // A circle around the center of the disk, moved by the isometry taking the center of the disk to `center`
+        let euclidean_radius = (self.radius / 2.).tanh();
+        let inverse_center = PoincarePoint::new(-self.center.0[0], -self.center.0[1]);
+        let point_at = |angle: f64| {
+            PoincarePoint::new(euclidean_radius * angle.cos(), euclidean_radius * angle.sin())
+                .transform_to_origin(&inverse_center, 0.)
+        };
+        let half = std::f64::consts::PI;
+
+        [0., half]
+            .iter()
+            .map(|angle| PoincareWall {
+                beginning: point_at(*angle),
+                end: point_at(angle + half),
+                color: self.color.clone(),
+                material_name: self.material.clone(),
+                material: None,
+                reflective: false,
+                lightmap: None,
+                curvature: 1. / self.radius.tanh(),
+                through: Some(point_at(angle + half / 2.)),
+            })
+            .collect()
+    }
+}
+
 impl PoincareWall {
-    /// Constructs a geodesic between wall ends in the Poncarie disk model.
+    /// Constructs a geodesic between wall ends in the Poncarie disk model,
+    /// or the circle of a curved wall.
     /// Returns: a triplet (x_center, y_center, radius)
     fn find_circle_through_points(&self) -> (f64, f64, f64) {
+        if let Some(through) = &self.through {
+            return Self::find_circle_through_three_points(&self.beginning, through, &self.end);
+        }
+
         //https://math.stackexchange.com/questions/1503466/algebraic-solutions-for-poincar%C3%A9-disk-arcs
         let p = self.beginning.0;
         let q = self.end.0;
@@ -101,7 +220,7 @@ impl PoincareWall {
         let (qx, qy): (f64, f64) = (q[0], q[1]);
 
         let (px2, py2) = (px.powi(2), py.powi(2));
-        let (qx2, qy2) = (px.powi(2), py.powi(2));
+        let (qx2, qy2) = (qx.powi(2), qy.powi(2));
 
         //circle center
         let x0 = (qy * (px2 + py2 + 1.) - py * (qx2 + qy2 + 1.)) / (2. * (px * qy - py * qx));
@@ -116,6 +235,102 @@ impl PoincareWall {
         (x0, y0, r0)
     }
 
+    /// Finds the circle through three points, which must not lie on a line.
+    /// Returns: a triplet (x_center, y_center, radius)
+    fn find_circle_through_three_points(
+        a: &PoincarePoint,
+        b: &PoincarePoint,
+        c: &PoincarePoint,
+    ) -> (f64, f64, f64) {
+        let (ax, ay) = (a.0[0], a.0[1]);
+        let (bx, by) = (b.0[0], b.0[1]);
+        let (cx, cy) = (c.0[0], c.0[1]);
+        let (a2, b2, c2) = (ax.powi(2) + ay.powi(2), bx.powi(2) + by.powi(2), cx.powi(2) + cy.powi(2));
+
+        let d = 2. * (ax * (by - cy) + bx * (cy - ay) + cx * (ay - by));
+        let x0 = (a2 * (by - cy) + b2 * (cy - ay) + c2 * (ay - by)) / d;
+        let y0 = (a2 * (cx - bx) + b2 * (ax - cx) + c2 * (bx - ax)) / d;
+        let r0 = ((ax - x0).powi(2) + (ay - y0).powi(2)).sqrt();
+
+        (x0, y0, r0)
+    }
+
+    /// Checks that there is an arc between the wall ends with the wall's curvature, see `find_curve_midpoint`:
+    /// the ends of an arc of a circle can be at most its diameter apart, only geodesics, hypercycles
+    /// and horocycles can reach infinity, and only geodesics and hypercycles can have both ends there.
+    /// Returns a description of the problem for the map's author if there is none.
+    pub fn check_curvature(&self) -> Result<(), String> {
+        let curvature = self.curvature.abs();
+        let fits = match (self.beginning.is_ideal(), self.end.is_ideal()) {
+            (true, true) => curvature < 1.,
+            (true, false) | (false, true) => curvature <= 1.,
+            (false, false) => {
+                // Curves with geodesic curvature k are circles of Euclidean diameter 1/k at the center of the disk
+                let end = self.end.transform_to_origin(&self.beginning, 0.);
+                curvature * PoincarePoint::minkowski_dot(&end, &end).sqrt() <= 1. + 1e-9
+            }
+        };
+        match fits {
+            true => Ok(()),
+            false => Err(format!(
+                "The wall from [{}, {}] to [{}, {}] cannot have the curvature {}",
+                self.beginning.0[0], self.beginning.0[1], self.end.0[0], self.end.0[1], self.curvature
+            )),
+        }
+    }
+
+    /// Finds the middle point of the arc between the wall ends with the given geodesic curvature,
+    /// turning left on the way from the beginning to the end if positive (see `curvature`).
+    /// Arcs of circles are the shorter of the two arcs between the ends.
+    /// The curvature has to fit the ends, see `check_curvature`.
+    pub fn find_curve_midpoint(&self, curvature: f64) -> PoincarePoint {
+        if self.beginning.is_ideal() {
+            if self.end.is_ideal() {
+                return self.find_complete_hypercycle_midpoint(curvature);
+            }
+            // Walk the same arc the other way
+            let reversed = PoincareWall {
+                beginning: self.end.clone(),
+                end: self.beginning.clone(),
+                ..self.clone()
+            };
+            return reversed.find_curve_midpoint(-curvature);
+        }
+
+        // Move the beginning to the center of the disk and the end onto the positive X axis.
+        // Curves through the center of the disk with geodesic curvature k are circles of
+        // Euclidean curvature 2k there.
+        let end = self.end.transform_to_origin(&self.beginning, 0.);
+        let angle = end.0[1].atan2(end.0[0]);
+        let length = PoincarePoint::minkowski_dot(&end, &end).sqrt();
+
+        let radius = 1. / (2. * curvature.abs());
+        let height = (radius.powi(2) - length.powi(2) / 4.).max(0.).sqrt();
+
+        // The center of curvature is on the left when turning left, the arc bulges the other way
+        let midpoint = PoincarePoint::new(length / 2., curvature.signum() * (height - radius));
+
+        let inverse_beginning = PoincarePoint::new(-self.beginning.0[0], -self.beginning.0[1]);
+        midpoint.rotate(angle).transform_to_origin(&inverse_beginning, 0.)
+    }
+
+    /// Middle point of a hypercycle between two ideal points, see `find_curve_midpoint`.
+    fn find_complete_hypercycle_midpoint(&self, curvature: f64) -> PoincarePoint {
+        // Rotate the ends to symmetric positions around the positive X axis
+        let beginning_angle = self.beginning.0[1].atan2(self.beginning.0[0]);
+        let half_angle = Self::wrap_angle(self.end.0[1].atan2(self.end.0[0]) - beginning_angle) / 2.;
+        let rotation = beginning_angle + half_angle;
+
+        // The axis crosses the X axis at sec - tan of the half angle, the hypercycle is atanh(curvature)
+        // away from it, on the right of the axis if turning left. That is further away from the center
+        // if the beginning is below the X axis, closer to it if the beginning is above.
+        let axis = (1. - half_angle.abs().sin()) / half_angle.cos();
+        let axis_distance = 2. * axis.atanh();
+        let midpoint = ((axis_distance + half_angle.signum() * curvature.atanh()) / 2.).tanh();
+
+        PoincarePoint::new(midpoint, 0.).rotate(rotation)
+    }
+
     /// Returns the angle of a point on the walls geodesic circle.
     /// Angle calculated relative to the X axis, eg.
     /// assuming center point of x: 0, y: 0
@@ -133,65 +348,479 @@ impl PoincareWall {
         }
     }
 
-    /// Checks whether p is located within the arc inscribed by endpoints of
-    /// the wall on the geodesic circle.
-    fn is_point_on_wall(&self, p: PoincarePoint, x0: f64, y0: f64) -> bool {
+    /// Wraps an angle difference into -PI..PI.
+    fn wrap_angle(angle: f64) -> f64 {
+        let pi = std::f64::consts::PI;
+        let wrapped = angle.rem_euclid(2. * pi);
+        match wrapped > pi {
+            true => wrapped - 2. * pi,
+            false => wrapped,
+        }
+    }
+
+    /// Returns the angle of the wall's beginning on the geodesic circle, and the signed
+    /// angle swept from the beginning to the end. The geodesic is the part of the circle
+    /// inside the disk, which is always the shorter of the two arcs. A curved wall
+    /// is the arc going through its middle point.
+    fn find_arc_on_wall(&self, x0: f64, y0: f64) -> (f64, f64) {
         let (x1, y1): (f64, f64) = (self.beginning.0[0], self.beginning.0[1]);
         let (x2, y2): (f64, f64) = (self.end.0[0], self.end.0[1]);
-        let (xp, yp): (f64, f64) = (p.0[0], p.0[1]);
 
         let angle1 = self.find_angle_on_wall(x1, y1, x0, y0);
         let angle2 = self.find_angle_on_wall(x2, y2, x0, y0);
-        let anglep = self.find_angle_on_wall(xp, yp, x0, y0);
 
-        let (anglemin, anglemax) = (angle1.min(angle2), angle1.max(angle2));
-        let result;
-        if anglep < anglemin || anglep > anglemax {
-            result = false;
-        } else {
-            result = true;
+        match &self.through {
+            Some(through) => {
+                let full_turn = 2. * std::f64::consts::PI;
+                let anglem = self.find_angle_on_wall(through.0[0], through.0[1], x0, y0);
+                let sweep = (angle2 - angle1).rem_euclid(full_turn);
+                match (anglem - angle1).rem_euclid(full_turn) <= sweep {
+                    true => (angle1, sweep),
+                    false => (angle1, sweep - full_turn),
+                }
+            }
+            None => (angle1, Self::wrap_angle(angle2 - angle1)),
         }
-        //println!("min:{},max:{},p:{},{:?}",anglemin, anglemax, anglep, result);
-        result
     }
 
-    /// Finds distance from origin to the closest intersection point, if that point lies on the wall.
-    /// Uses Poincare metric implemented on Point struct.
-    pub fn find_distance_of_intersection_with_ray(&self, angle: f64) -> Option<f64> {
+    /// Returns a copy of the wall with all its points moved by the given transformation.
+    pub fn map_points(&self, transformation: impl Fn(&PoincarePoint) -> PoincarePoint) -> PoincareWall {
+        PoincareWall {
+            beginning: transformation(&self.beginning),
+            end: transformation(&self.end),
+            through: self.through.as_ref().map(&transformation),
+            ..self.clone()
+        }
+    }
+
+    /// Checks whether p is located within the arc inscribed by endpoints of
+    /// the wall on the geodesic circle.
+    fn is_point_on_wall(&self, p: PoincarePoint, x0: f64, y0: f64) -> bool {
+        let (xp, yp): (f64, f64) = (p.0[0], p.0[1]);
+
+        let (start, sweep) = self.find_arc_on_wall(x0, y0);
+        let offset = Self::wrap_angle(self.find_angle_on_wall(xp, yp, x0, y0) - start);
+
+        match sweep < 0. {
+            true => offset <= 0. && offset >= sweep,
+            false => offset >= 0. && offset <= sweep,
+        }
+    }
+
+    /// Returns points along the wall, from beginning to end, on its geodesic arc.
+    /// Consecutive points are at most `max_step` apart (in Euclidean coordinates of the disk),
+    /// so that connecting them with straight lines draws the arc.
+    /// Walls through the center of the disk are straight lines, so only the ends are returned.
+    pub fn points_along(&self, max_step: f64) -> Vec<PoincarePoint> {
+        let (x0, y0, r) = self.find_circle_through_points();
+        if !(x0.is_finite() && y0.is_finite() && r.is_finite()) {
+            return vec![self.beginning.clone(), self.end.clone()];
+        }
+
+        let (start, sweep) = self.find_arc_on_wall(x0, y0);
+        let steps = ((sweep.abs() * r / max_step).ceil() as usize).max(1);
+
+        (0..=steps)
+            .map(|step| {
+                let angle = start + sweep * step as f64 / steps as f64;
+                PoincarePoint::new(x0 + r * angle.cos(), y0 + r * angle.sin())
+            })
+            .collect()
+    }
+
+    /// Length of the wall between two of its points, measured along the wall in the Poincare metric.
+    /// A curved wall is longer than the geodesic between the points, so its length is
+    /// added up along the arc with Simpson's rule.
+    pub fn length_along(&self, from: &PoincarePoint, to: &PoincarePoint) -> f64 {
+        if self.through.is_none() {
+            return from.distance_to(to);
+        }
+
+        let (x0, y0, r) = self.find_circle_through_points();
+        let (start, sweep) = self.find_arc_on_wall(x0, y0);
+        let full_turn = 2. * std::f64::consts::PI;
+
+        // Angle of a point from the beginning, in the direction of the sweep. Points rounded
+        // off the arc are put back on the nearer end.
+        let offset = |p: &PoincarePoint| {
+            let angle = self.find_angle_on_wall(p.0[0], p.0[1], x0, y0);
+            let offset = ((angle - start) * sweep.signum()).rem_euclid(full_turn);
+            let offset = match offset > sweep.abs() {
+                true if offset - sweep.abs() > full_turn - offset => 0.,
+                true => sweep.abs(),
+                false => offset,
+            };
+            start + offset * sweep.signum()
+        };
+        let (a, b) = (offset(from), offset(to));
+
+        // A step of dθ along the circle is 2r dθ / (1 - |z|²) long
+        let length_element = |angle: f64| {
+            let (x, y) = (x0 + r * angle.cos(), y0 + r * angle.sin());
+            2. * r / (1. - x.powi(2) - y.powi(2))
+        };
+        let steps = 64;
+        let h = (b - a) / steps as f64;
+        let sum: f64 = (0..=steps)
+            .map(|step| {
+                let weight = match step {
+                    0 => 1.,
+                    _ if step == steps => 1.,
+                    _ if step % 2 == 1 => 4.,
+                    _ => 2.,
+                };
+                weight * length_element(a + h * step as f64)
+            })
+            .sum();
+        (h * sum / 3.).abs()
+    }
+
+    /// Finds the closest intersection point with a ray from the origin, if that point lies on the wall.
+    /// The ray can point in any direction, `angle` is measured from the positive X axis.
+    /// Returns the distance from origin to the point, using Poincare metric implemented on Point struct,
+    /// along with the point itself.
+    pub fn find_intersection_with_ray(&self, angle: f64) -> Option<(f64, PoincarePoint)> {
+        self.find_intersection_with_ray_beyond(angle, 0.)
+    }
+
+    /// Same as `find_intersection_with_ray`, but ignores intersections
+    /// closer to the origin than `min_distance`.
+    pub fn find_intersection_with_ray_beyond(
+        &self,
+        angle: f64,
+        min_distance: f64,
+    ) -> Option<(f64, PoincarePoint)> {
         let (a, b, r) = self.find_circle_through_points();
-        let m = angle; //.tan();
-        let r2 = r.powi(2);
-        let m2 = m.powi(2);
+        let (dx, dy) = (angle.cos(), angle.sin());
 
-        let delta = r2 * (1. + m2) - (b - m * a).powi(2);
-        let deltasqrt = delta.sqrt();
+        // Points t * (dx, dy) of the ray on the circle solve t^2 - 2kt + (a^2 + b^2 - r^2) = 0,
+        // where the constant is 1 for circles orthogonal to the boundary of the disk
+        let k = a * dx + b * dy;
+        let c = a.powi(2) + b.powi(2) - r.powi(2);
+        let deltasqrt = (k.powi(2) - c).sqrt();
         if deltasqrt.is_nan() {
             return None;
         }
 
-        let x1 = (a + b * m + deltasqrt) / (1. + m2);
-        let x2 = (a + b * m - deltasqrt) / (1. + m2);
+        let mut points: Vec<(f64, PoincarePoint)> = [k - deltasqrt, k + deltasqrt]
+            .iter()
+            // Only points in the direction of the ray, not behind it
+            .filter(|t| **t > 0.)
+            .map(|t| {
+                let point = PoincarePoint::new(t * dx, t * dy);
+                (point.distance_to_origin(), point)
+            })
+            .collect();
+        points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
 
-        let y1 = (a * m + b * m2 + m * deltasqrt) / (1. + m2);
-        let y2 = (a * m + b * m2 - m * deltasqrt) / (1. + m2);
+        points.into_iter().find(|e| {
+            !e.0.is_nan() && e.0 > min_distance && self.is_point_on_wall(e.1.clone(), a, b)
+        })
+    }
 
-        let p1 = PoincarePoint::new(x1, y1);
-        let p2 = PoincarePoint::new(x2, y2);
+    /// Reflects a point across the wall's geodesic. In the Poincare disk model
+    /// this is the inversion in the geodesic's circle, an isometry of the hyperbolic plane.
+    pub fn reflect_point(&self, point: &PoincarePoint) -> PoincarePoint {
+        let (x0, y0, r) = self.find_circle_through_points();
+        let (dx, dy) = (point.0[0] - x0, point.0[1] - y0);
+        let scale = r.powi(2) / (dx.powi(2) + dy.powi(2));
+        PoincarePoint::new(x0 + dx * scale, y0 + dy * scale)
+    }
 
-        let d1 = p1.distance_to_origin();
-        let d2 = p2.distance_to_origin();
+    /// Returns a copy of another wall, reflected across this wall's geodesic.
+    /// The ends are swapped, so the same side of the wall stays in front.
+    pub fn reflect_wall(&self, wall: &PoincareWall) -> PoincareWall {
+        let reflected = wall.map_points(|point| self.reflect_point(point));
+        PoincareWall {
+            beginning: reflected.end,
+            end: reflected.beginning,
+            lightmap: wall
+                .lightmap
+                .as_ref()
+                .map(|lightmap| Rc::new(lightmap.iter().rev().cloned().collect())),
+            ..reflected
+        }
+    }
 
-        let mut points = vec![(d1, p1), (d2, p2)];
-        points.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+    /// Whether a ray from the origin in the direction of `angle` hits the front of the wall at `point`,
+    /// the side on the left of the way from the wall's beginning to its end.
+    pub fn is_front_facing(&self, angle: f64, point: &PoincarePoint) -> bool {
+        let (dx, dy) = (angle.cos(), angle.sin());
+        let (x0, y0, _) = self.find_circle_through_points();
+
+        // The front is to the left of the wall's direction at the hit point, the ray comes from there
+        // if it moves to the right of it
+        let (tx, ty) = match x0.is_finite() && y0.is_finite() {
+            true => {
+                let (_, sweep) = self.find_arc_on_wall(x0, y0);
+                let (nx, ny) = (point.0[0] - x0, point.0[1] - y0);
+                (-ny * sweep.signum(), nx * sweep.signum())
+            }
+            false => (self.end.0[0] - self.beginning.0[0], self.end.0[1] - self.beginning.0[1]),
+        };
+        tx * dy - ty * dx < 0.
+    }
 
-        let (closest_distance, closest_point) = points.into_iter().find(|e| {
-            let x = (e.1).0[0];
-            x > 0. && !e.0.is_nan()
-        })?;
+    /// Checks whether the wall crosses the geodesic segment between two points,
+    /// e.g. whether it stands between a light and a lit point.
+    ///
+    /// Moves `from` to the origin and `to` onto the positive X axis, so the segment
+    /// becomes a ray from the origin, and reuses the ray intersection.
+    pub fn blocks_segment(&self, from: &PoincarePoint, to: &PoincarePoint) -> bool {
+        let target = to.transform_to_origin(from, 0.);
+        let angle = target.0[1].atan2(target.0[0]);
+        let length = target.distance_to_origin();
+
+        let transformed = self.map_points(|point| point.transform_to_origin(from, angle));
+
+        // Intersections right at the end of the segment are the lit point itself
+        match transformed.find_intersection_with_ray(0.) {
+            Some((distance, _)) => distance < length - 1e-6,
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A wall between two points, as read from a map file.
+    fn wall(x1: f64, y1: f64, x2: f64, y2: f64) -> PoincareWall {
+        serde_json::from_str(&format!(
+            r#"{{"beginning": [{}, {}], "end": [{}, {}], "color": {{"red": 0, "green": 0, "blue": 0}}}}"#,
+            x1, y1, x2, y2
+        ))
+        .unwrap()
+    }
+
+    /// A wall across a direction, its ends at the same distance from the center on both sides.
+    fn wall_across(angle: f64, radius: f64, half_width: f64) -> PoincareWall {
+        let (left, right) = (angle - half_width, angle + half_width);
+        wall(radius * left.cos(), radius * left.sin(), radius * right.cos(), radius * right.sin())
+    }
+
+    /// The slope of a ray is tan(angle), so the same wall is hit at the same distance whichever way it is turned.
+    #[test]
+    fn rays_are_cast_in_their_direction() {
+        let distance = |angle: f64| {
+            let wall = wall_across(angle, 0.6, 0.2);
+            wall.find_intersection_with_ray(angle).map(|(distance, _)| distance)
+        };
+        let ahead = distance(0.5).unwrap();
+        let turned = distance(1.0).unwrap();
+        assert!((ahead - turned).abs() < 1e-9, "{} != {}", ahead, turned);
+    }
+
+    /// The geodesic's circle goes through both ends of the wall and meets the boundary of the disk at right angles.
+    #[test]
+    fn geodesic_circle_passes_through_both_ends() {
+        let wall = wall(0.1, 0.5, 0.6, -0.2);
+        let (x0, y0, r) = wall.find_circle_through_points();
+        for end in &[&wall.beginning, &wall.end] {
+            let distance = ((end.0[0] - x0).powi(2) + (end.0[1] - y0).powi(2)).sqrt();
+            assert!((distance - r).abs() < 1e-9, "{:?} is {} from the center, not {}", end, distance, r);
+        }
+        assert!((x0.powi(2) + y0.powi(2) - r.powi(2) - 1.).abs() < 1e-9);
+    }
+
+    /// The arc of a wall may cross angle 0 of its circle, seen from the circle's center.
+    #[test]
+    fn arc_crossing_angle_zero_contains_its_middle() {
+        let wall = wall(-0.2, 0.5, -0.2, -0.5);
+        let (x0, y0, r) = wall.find_circle_through_points();
+        assert!(wall.is_point_on_wall(PoincarePoint::new(x0 + r, y0), x0, y0));
+        assert!(!wall.is_point_on_wall(PoincarePoint::new(x0 - r, y0), x0, y0));
+        assert!(!wall.is_point_on_wall(PoincarePoint::new(x0, y0 + r), x0, y0));
+    }
+
+    /// The squared norm of a point of the disk is x² + y², which keeps distances when moving to the hyperboloid.
+    #[test]
+    fn hyperboloid_point_keeps_the_distance_from_the_origin() {
+        let point = PoincarePoint::new(0.5, 0.5);
+        assert!((PoincarePoint::minkowski_dot(&point, &point) - 0.5).abs() < 1e-12);
+
+        let hyperpoint = Hyperpoint::from(point.clone());
+        let expected = 2. * 0.5f64.sqrt().atanh();
+        assert!((point.distance_to_origin() - expected).abs() < 1e-9);
+        assert!((hyperpoint.distance_to_origin() - expected).abs() < 1e-9);
+    }
+
+    #[test]
+    fn wall_across_a_segment_blocks_it() {
+        let wall = wall(0.1, -0.5, 0.1, 0.5);
+        let (from, to) = (PoincarePoint::new(-0.5, 0.1), PoincarePoint::new(0.6, -0.1));
+        assert!(wall.blocks_segment(&from, &to));
+        assert!(wall.blocks_segment(&to, &from));
+    }
+
+    #[test]
+    fn wall_beside_or_beyond_a_segment_does_not_block_it() {
+        let (from, to) = (PoincarePoint::new(-0.5, 0.1), PoincarePoint::new(0., -0.1));
+        assert!(!wall(0.3, -0.5, 0.3, 0.5).blocks_segment(&from, &to));
+        assert!(!wall(-0.2, 0.3, 0.2, 0.6).blocks_segment(&from, &to));
+        assert!(!wall(-0.8, -0.5, -0.7, 0.5).blocks_segment(&from, &to));
+    }
+
+    #[test]
+    fn wall_does_not_block_a_segment_ending_on_it() {
+        let wall = wall(0.1, -0.5, 0.1, 0.5);
+        let (x0, y0, r) = wall.find_circle_through_points();
+        let on_wall = PoincarePoint::new(x0 - r, y0);
+        assert!(wall.is_point_on_wall(on_wall.clone(), x0, y0));
+        assert!(!wall.blocks_segment(&PoincarePoint::new(-0.5, 0.), &on_wall));
+    }
+
+    /// Reflecting twice across a mirror gives back the point, and points on the mirror stay where they are.
+    #[test]
+    fn reflection_is_an_involution_fixing_the_wall() {
+        let mirror = wall(0.1, 0.5, 0.6, -0.2);
+        for point in &[PoincarePoint::new(0., 0.), PoincarePoint::new(-0.3, 0.4), PoincarePoint::new(0.2, -0.7)] {
+            let reflected = mirror.reflect_point(point);
+            assert!(reflected.distance_to(point) > 0.1, "{:?} is not moved", point);
+
+            let back = mirror.reflect_point(&reflected);
+            assert!(back.distance_to(point) < 1e-9, "{:?} comes back as {:?}", point, back);
+        }
+        for point in mirror.points_along(0.05) {
+            let reflected = mirror.reflect_point(&point);
+            assert!(reflected.distance_to(&point) < 1e-9, "{:?} is moved to {:?}", point, reflected);
+        }
+    }
+
+    /// The reflection is an isometry, it keeps the distance between points.
+    #[test]
+    fn reflection_keeps_distances() {
+        let mirror = wall(0.1, 0.5, 0.6, -0.2);
+        let (a, b) = (PoincarePoint::new(-0.3, 0.4), PoincarePoint::new(0.2, -0.7));
+        let (reflected_a, reflected_b) = (mirror.reflect_point(&a), mirror.reflect_point(&b));
+        assert!((reflected_a.distance_to(&reflected_b) - a.distance_to(&b)).abs() < 1e-9);
+    }
+
+    /// The point at the given angle on the boundary of the disk.
+    fn ideal(angle: f64) -> (f64, f64) {
+        (angle.cos(), angle.sin())
+    }
+
+    /// Whether two points are the same, up to rounding.
+    fn assert_same(a: &PoincarePoint, b: &PoincarePoint) {
+        assert!((a.0 - b.0).norm() < 1e-9, "{:?} != {:?}", a, b);
+    }
+
+    /// A hypercycle between two ideal points bulges the same way whichever end it starts at:
+    /// turning left from the beginning below the X axis, or right from the beginning above it.
+    #[test]
+    fn hypercycle_midpoint_turns_the_same_way_in_both_directions() {
+        let ((x1, y1), (x2, y2)) = (ideal(-0.5), ideal(0.5));
+        let upwards = wall(x1, y1, x2, y2);
+        let downwards = wall(x2, y2, x1, y1);
+        let axis = upwards.find_curve_midpoint(0.).0[0];
+
+        // Turning left on the way up bulges away from the center
+        let outer = upwards.find_curve_midpoint(0.5);
+        assert!(outer.0[0] > axis && outer.0[1].abs() < 1e-9, "{:?} is not beyond {}", outer, axis);
+        assert_same(&outer, &downwards.find_curve_midpoint(-0.5));
+
+        // Turning left on the way down bulges towards the center
+        let inner = downwards.find_curve_midpoint(0.5);
+        assert!(inner.0[0] < axis && inner.0[1].abs() < 1e-9, "{:?} is not before {}", inner, axis);
+        assert_same(&inner, &upwards.find_curve_midpoint(-0.5));
+
+        // Both are atanh(curvature) away from the axis
+        let axis = PoincarePoint::new(axis, 0.);
+        assert!((outer.distance_to(&axis) - 0.5f64.atanh()).abs() < 1e-9);
+        assert!((inner.distance_to(&axis) - 0.5f64.atanh()).abs() < 1e-9);
+    }
+
+    /// A curve through the center of the disk with geodesic curvature k is a circle of Euclidean radius 1/2k there.
+    #[test]
+    fn curve_midpoint_from_the_center() {
+        let midpoint = wall(0., 0., 0.4, 0.).find_curve_midpoint(0.5);
+        assert_same(&midpoint, &PoincarePoint::new(0.2, 0.96f64.sqrt() - 1.));
+    }
+
+    /// The curve between finite ends has the curvature asked for, whichever end it starts at.
+    /// A circle with center c and radius r on the disk has geodesic curvature (1 + r² - |c|²) / 2r.
+    #[test]
+    fn curve_midpoint_between_finite_ends() {
+        let forwards = wall(0.1, 0.3, 0.5, -0.2);
+        let backwards = wall(0.5, -0.2, 0.1, 0.3);
+        for curvature in &[0.5, 1., 1.5] {
+            let midpoint = forwards.find_curve_midpoint(*curvature);
+            let (x0, y0, r) =
+                PoincareWall::find_circle_through_three_points(&forwards.beginning, &midpoint, &forwards.end);
+            let found = (1. + r.powi(2) - x0.powi(2) - y0.powi(2)) / (2. * r);
+            assert!((found - curvature).abs() < 1e-9, "{} != {}", found, curvature);
+
+            // The same circle
+            let reversed = backwards.find_curve_midpoint(-curvature);
+            let circle =
+                PoincareWall::find_circle_through_three_points(&backwards.beginning, &reversed, &backwards.end);
+            assert!((circle.0 - x0).abs() < 1e-9 && (circle.1 - y0).abs() < 1e-9 && (circle.2 - r).abs() < 1e-9);
+        }
+
+        // Turning left bulges to the right of the way from the beginning to the end
+        let midpoint = forwards.find_curve_midpoint(1.5);
+        let (way, side) = (forwards.end.0 - forwards.beginning.0, midpoint.0 - forwards.beginning.0);
+        assert!(way[0] * side[1] - way[1] * side[0] < 0., "{:?} is on the left", midpoint);
+    }
+
+    #[test]
+    fn curvature_has_to_fit_the_ends() {
+        let with_curvature = |mut wall: PoincareWall, curvature: f64| {
+            wall.curvature = curvature;
+            wall
+        };
+        let ((x1, y1), (x2, y2)) = (ideal(-0.5), ideal(0.5));
+
+        // Ends 0.4 apart at the center fit on circles down to a diameter of 0.4
+        assert!(with_curvature(wall(0., 0., 0.4, 0.), 2.5).check_curvature().is_ok());
+        assert!(with_curvature(wall(0., 0., 0.4, 0.), -2.6).check_curvature().is_err());
+
+        // Horocycles reach one ideal point, hypercycles two
+        assert!(with_curvature(wall(0., 0., x2, y2), 1.).check_curvature().is_ok());
+        assert!(with_curvature(wall(x1, y1, 0., 0.), 1.1).check_curvature().is_err());
+        assert!(with_curvature(wall(x1, y1, x2, y2), 0.9).check_curvature().is_ok());
+        assert!(with_curvature(wall(x1, y1, x2, y2), -1.).check_curvature().is_err());
+    }
+
+    /// The circumference of a circle of radius R is 2π sinh(R), and half of it is on each of its walls.
+    #[test]
+    fn curved_walls_are_measured_along_their_arc() {
+        let circle = PoincareCircle {
+            center: PoincarePoint::new(0.3, -0.2),
+            radius: 1.,
+            color: RGBColor::black(),
+            material: None,
+        };
+        let half = std::f64::consts::PI * 1f64.sinh();
+        for wall in circle.into_walls() {
+            let through = wall.through.clone().unwrap();
+            assert!((wall.length_along(&wall.beginning, &wall.end) - half).abs() < 1e-6);
+            assert!((wall.length_along(&wall.beginning, &through) - half / 2.).abs() < 1e-6);
+            assert!((wall.length_along(&through, &wall.end) - half / 2.).abs() < 1e-6);
+            assert!(wall.length_along(&wall.beginning, &wall.beginning) < 1e-9);
+        }
+    }
+
+    /// Geodesic walls are as long as the distance between their ends.
+    #[test]
+    fn geodesic_walls_are_measured_by_distance() {
+        let wall = wall(0.1, 0.5, 0.6, -0.2);
+        let length = wall.length_along(&wall.beginning, &wall.end);
+        assert!((length - wall.beginning.distance_to(&wall.end)).abs() < 1e-12);
+    }
 
-        match self.is_point_on_wall(closest_point, a, b) {
-            true => Some(closest_distance),
-            false => None,
+    #[test]
+    fn circle_walls_fit_their_curvature() {
+        let circle = PoincareCircle {
+            center: PoincarePoint::new(-0.5, 0.2),
+            radius: 0.3,
+            color: RGBColor::black(),
+            material: None,
+        };
+        for wall in circle.into_walls() {
+            assert!(wall.check_curvature().is_ok());
         }
     }
 }
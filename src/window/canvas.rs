@@ -1,6 +1,5 @@
-extern crate sdl2;
-
-use crate::utils::color::RGBColor;
+use super::font::{self, ADVANCE, GLYPH_HEIGHT, GLYPH_WIDTH};
+use crate::utils::color::{LinearColor, RGBColor};
 
 /// A buffer for pixel drawing.
 pub struct Canvas<'a> {
@@ -31,6 +30,12 @@ impl<'a> Canvas<'a> {
         self.height
     }
 
+    /// Red, green and blue bytes of the pixels of a row, from the left.
+    pub fn row(&self, y: usize) -> &[u8] {
+        let start = y * self.pitch;
+        &self.buffer[start..start + self.width * 3]
+    }
+
     /// Draws a pixel at the given coordinates.
     pub fn draw_pixel(&mut self, x: usize, y: usize, color: &RGBColor) {
         let offset = y * self.pitch + x * 3;
@@ -39,6 +44,73 @@ impl<'a> Canvas<'a> {
         self.buffer[offset + 2] = color.blue;
     }
 
+    /// Draws a pixel of linear light at the given coordinates. The color is tone mapped
+    /// and dithered into sRGB.
+    pub fn draw_linear_pixel(&mut self, x: usize, y: usize, color: &LinearColor) {
+        self.draw_pixel(x, y, &color.tone_map().to_srgb_dithered(x, y));
+    }
+
+    /// Draws a pixel at the given coordinates, if they lie on the canvas.
+    pub fn try_draw_pixel(&mut self, x: i32, y: i32, color: &RGBColor) {
+        if x >= 0 && y >= 0 && (x as usize) < self.width && (y as usize) < self.height {
+            self.draw_pixel(x as usize, y as usize, color);
+        }
+    }
+
+    /// Fills the whole canvas with a color.
+    pub fn fill(&mut self, color: &RGBColor) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                self.draw_pixel(x, y, color);
+            }
+        }
+    }
+
+    /// Draws text in the built-in bitmap font, with its top left corner at the given coordinates.
+    /// Each pixel of the font is drawn as a `scale` × `scale` square. Parts outside of the canvas are cut off.
+    /// Returns the width of the text, in pixels.
+    pub fn draw_text(&mut self, x: usize, y: usize, text: &str, color: &RGBColor, scale: usize) -> usize {
+        let mut left = x;
+        for character in text.chars() {
+            for (row, bits) in font::glyph(character).iter().enumerate() {
+                for column in 0..GLYPH_WIDTH {
+                    if bits & (1 << (GLYPH_WIDTH - 1 - column)) == 0 {
+                        continue;
+                    }
+                    for dy in 0..scale {
+                        for dx in 0..scale {
+                            let pixel_x = left + column * scale + dx;
+                            let pixel_y = y + row * scale + dy;
+                            if pixel_x < self.width && pixel_y < self.height {
+                                self.draw_pixel(pixel_x, pixel_y, color);
+                            }
+                        }
+                    }
+                }
+            }
+            left += ADVANCE * scale;
+        }
+        left - x
+    }
+
+    /// The height of a line of text drawn by `draw_text`, in pixels.
+    pub fn text_height(scale: usize) -> usize {
+        GLYPH_HEIGHT * scale
+    }
+
+    /// Returns a canvas drawing into a rectangle of this canvas.
+    /// The rectangle must lie within the canvas.
+    pub fn sub_canvas(&mut self, x: usize, y: usize, width: usize, height: usize) -> Canvas<'_> {
+        let start = y * self.pitch + x * 3;
+        let end = start + (height.max(1) - 1) * self.pitch + width * 3;
+        Canvas {
+            buffer: &mut self.buffer[start..end],
+            pitch: self.pitch,
+            width,
+            height,
+        }
+    }
+
     /// Draws a pixel at the given coordinates.
     pub fn draw_pixel_big(&mut self, x: usize, y: usize, color: &RGBColor) {
         self.draw_pixel(x, y, &color);
@@ -18,26 +18,33 @@ impl EventSource {
         }
     }
 
-    /// Polls the next event from the source.
+    /// Polls the next event from the source, skipping events the application does not handle.
     pub fn poll_next_event(&mut self) -> Option<Event> {
-        if let Some(event) = self.event_pump.poll_iter().next() {
-            match event {
+        while let Some(event) = self.event_pump.poll_event() {
+            let event = match event {
                 SDLEvent::Quit { .. }
                 | SDLEvent::KeyDown {
                     keycode: Some(SDLKeycode::Escape),
                     ..
-                } => return Some(Event::Quit),
+                } => Some(Event::Quit),
+                SDLEvent::KeyDown {
+                    keycode: Some(sdl_keycode),
+                    repeat: false,
+                    ..
+                } => Keycode::from_sdl_keycode(sdl_keycode).map(Event::KeyPressed),
                 SDLEvent::Window { win_event, .. } => match win_event {
                     SDLWindowEvent::Resized(..) | SDLWindowEvent::SizeChanged(..) => {
-                        return Some(Event::Resize)
+                        Some(Event::Resize)
                     }
                     _ => None,
                 },
                 _ => None,
+            };
+            if event.is_some() {
+                return event;
             }
-        } else {
-            None
         }
+        None
     }
 
     /// Provides a set of key codes for the currently pressed keys.
@@ -65,6 +72,9 @@ pub enum Event {
 
     /// The window was resized.
     Resize,
+
+    /// A key was pressed down (not repeated while held), used for toggles.
+    KeyPressed(Keycode),
 }
 
 /// Keycodes to detect pressed keys
@@ -78,6 +88,29 @@ pub enum Keycode {
     W,
     S,
     D,
+    Q,
+    M,
+    Z,
+    X,
+    I,
+    J,
+    K,
+    L,
+    C,
+    R,
+    P,
+    Space,
+    PageUp,
+    PageDown,
+    LCtrl,
+    V,
+    B,
+    F,
+    H,
+    G,
+    Tab,
+    T,
+    Y,
 }
 
 impl Keycode {
@@ -92,6 +125,29 @@ impl Keycode {
             SDLKeycode::W => Some(Keycode::W),
             SDLKeycode::S => Some(Keycode::S),
             SDLKeycode::D => Some(Keycode::D),
+            SDLKeycode::Q => Some(Keycode::Q),
+            SDLKeycode::M => Some(Keycode::M),
+            SDLKeycode::Z => Some(Keycode::Z),
+            SDLKeycode::X => Some(Keycode::X),
+            SDLKeycode::I => Some(Keycode::I),
+            SDLKeycode::J => Some(Keycode::J),
+            SDLKeycode::K => Some(Keycode::K),
+            SDLKeycode::L => Some(Keycode::L),
+            SDLKeycode::C => Some(Keycode::C),
+            SDLKeycode::R => Some(Keycode::R),
+            SDLKeycode::P => Some(Keycode::P),
+            SDLKeycode::Space => Some(Keycode::Space),
+            SDLKeycode::PageUp => Some(Keycode::PageUp),
+            SDLKeycode::PageDown => Some(Keycode::PageDown),
+            SDLKeycode::LCtrl => Some(Keycode::LCtrl),
+            SDLKeycode::V => Some(Keycode::V),
+            SDLKeycode::B => Some(Keycode::B),
+            SDLKeycode::F => Some(Keycode::F),
+            SDLKeycode::H => Some(Keycode::H),
+            SDLKeycode::G => Some(Keycode::G),
+            SDLKeycode::Tab => Some(Keycode::Tab),
+            SDLKeycode::T => Some(Keycode::T),
+            SDLKeycode::Y => Some(Keycode::Y),
             _ => None,
         }
     }
@@ -1,13 +1,21 @@
+#[cfg(feature = "sdl")]
 extern crate sdl2;
 
 pub mod canvas;
+#[cfg(feature = "sdl")]
 pub mod event;
+pub mod font;
+pub mod resolution;
+#[cfg(feature = "sdl")]
 pub mod view;
 
+#[cfg(feature = "sdl")]
 use crate::window::event::EventSource;
+#[cfg(feature = "sdl")]
 use crate::window::view::View;
 
 /// A window that can be used for drawing and that provides user events. May be a window or a HTML canvas.
+#[cfg(feature = "sdl")]
 pub struct Window {
     /// The view that can be used for drawing
     pub view: View,
@@ -16,8 +24,10 @@ pub struct Window {
     pub event_source: EventSource,
 }
 
+#[cfg(feature = "sdl")]
 impl Window {
     /// Creates a new window.
+    #[allow(clippy::new_without_default)]
     pub fn new() -> Window {
         let sdl_context = sdl2::init().unwrap();
 
@@ -0,0 +1,204 @@
+use std::time::Duration;
+
+/// Lowers the resolution frames are rendered at when rendering takes longer
+/// than a target frame time, and raises it again when there is headroom.
+/// The rendered picture is then scaled up to the window.
+pub struct ResolutionScaler {
+    /// The time a frame should take at most.
+    pub target_frame_time: Duration,
+
+    /// The lowest allowed scale of the window's resolution.
+    pub minimum_scale: f64,
+
+    /// Current scale of the window's resolution (0…1).
+    scale: f64,
+
+    /// Moving average of recent frame times, in seconds.
+    average_frame_time: f64,
+
+    /// How long no frame has been rendered for.
+    idle_time: Duration,
+}
+
+impl ResolutionScaler {
+    /// Creates a scaler aiming at the given number of frames per second, starting at full resolution.
+    pub fn new(target_fps: f64) -> ResolutionScaler {
+        ResolutionScaler {
+            target_frame_time: Duration::from_secs_f64(1.0 / target_fps),
+            minimum_scale: 0.25,
+            scale: 1.0,
+            average_frame_time: 0.0,
+            idle_time: Duration::ZERO,
+        }
+    }
+
+    /// Current scale of the window's resolution (0…1).
+    pub fn scale(&self) -> f64 {
+        self.scale
+    }
+
+    /// The resolution to render at for a window of the given size.
+    pub fn scaled_size(&self, width: u32, height: u32) -> (u32, u32) {
+        (
+            ((width as f64 * self.scale) as u32).max(1),
+            ((height as f64 * self.scale) as u32).max(1),
+        )
+    }
+
+    /// How long no frame has to be rendered for before the scale starts recovering.
+    const RECOVERY_DELAY: Duration = Duration::from_millis(500);
+
+    /// Adjusts the scale after rendering a frame took the given time.
+    pub fn update(&mut self, frame_time: Duration) {
+        self.idle_time = Duration::ZERO;
+        let frame_time = frame_time.as_secs_f64();
+        let target = self.target_frame_time.as_secs_f64();
+
+        // Smooth out single slow or fast frames
+        self.average_frame_time = if self.average_frame_time == 0.0 {
+            frame_time
+        } else {
+            0.8 * self.average_frame_time + 0.2 * frame_time
+        };
+
+        // Rendering time grows with the number of pixels, the square of the scale
+        if self.average_frame_time > target {
+            let step = (target / self.average_frame_time).sqrt().max(0.9);
+            self.scale = (self.scale * step).max(self.minimum_scale);
+        } else if self.average_frame_time < target * 0.7 {
+            self.scale = (self.scale * 1.02).min(1.0);
+        }
+    }
+    /// Lets the scale recover while no frames are rendered, e.g. while waiting for input,
+    /// so a few slow frames do not keep the resolution low until the next fast ones.
+    /// After `RECOVERY_DELAY`, the scale grows as if fast frames were rendered all along.
+    ///
+    /// # Parameters:
+    ///     - elapsed:      The time since the last update, with no frame rendered.
+    pub fn idle(&mut self, elapsed: Duration) {
+        self.idle_time += elapsed;
+        if self.idle_time < Self::RECOVERY_DELAY {
+            return;
+        }
+
+        // The frames measured before no longer tell how fast the next ones will be
+        self.average_frame_time = 0.0;
+        let frames = elapsed.as_secs_f64() / self.target_frame_time.as_secs_f64();
+        self.scale = (self.scale * 1.02f64.powf(frames)).min(1.0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A scaler aiming at 10 frames per second, that is 100 ms per frame.
+    fn scaler() -> ResolutionScaler {
+        ResolutionScaler::new(10.0)
+    }
+
+    #[test]
+    fn slow_frames_lower_the_scale_down_to_the_minimum() {
+        let mut scaler = scaler();
+        scaler.update(Duration::from_millis(200));
+        assert!(scaler.scale() < 1.0);
+        assert!(scaler.scale() >= 0.9, "lowered by more than 10% at once: {}", scaler.scale());
+
+        for _ in 0..100 {
+            scaler.update(Duration::from_millis(200));
+        }
+        assert_eq!(scaler.scale(), scaler.minimum_scale);
+    }
+
+    #[test]
+    fn fast_frames_raise_the_scale_up_to_full_resolution() {
+        let mut scaler = scaler();
+        for _ in 0..10 {
+            scaler.update(Duration::from_millis(200));
+        }
+        let lowered = scaler.scale();
+
+        for _ in 0..50 {
+            scaler.update(Duration::from_millis(10));
+        }
+        assert!(scaler.scale() > lowered);
+
+        for _ in 0..1000 {
+            scaler.update(Duration::from_millis(10));
+        }
+        assert_eq!(scaler.scale(), 1.0);
+    }
+
+    #[test]
+    fn frames_close_to_the_target_keep_the_scale() {
+        let mut scaler = scaler();
+        for _ in 0..10 {
+            scaler.update(Duration::from_millis(200));
+        }
+        let lowered = scaler.scale();
+        // The slow frames still weigh on the average at first
+        for _ in 0..20 {
+            scaler.update(Duration::from_millis(90));
+        }
+        let settled = scaler.scale();
+        assert!(settled <= lowered);
+
+        for _ in 0..10 {
+            scaler.update(Duration::from_millis(90));
+        }
+        assert_eq!(scaler.scale(), settled);
+    }
+
+    #[test]
+    fn scale_recovers_while_idle() {
+        let mut scaler = scaler();
+        for _ in 0..100 {
+            scaler.update(Duration::from_millis(200));
+        }
+
+        // Not before the delay
+        scaler.idle(Duration::from_millis(400));
+        assert_eq!(scaler.scale(), scaler.minimum_scale);
+
+        scaler.idle(Duration::from_millis(200));
+        assert!(scaler.scale() > scaler.minimum_scale);
+
+        for _ in 0..100 {
+            scaler.idle(Duration::from_millis(100));
+        }
+        assert_eq!(scaler.scale(), 1.0);
+    }
+
+    #[test]
+    fn rendering_restarts_the_recovery_delay() {
+        let mut scaler = scaler();
+        for _ in 0..100 {
+            scaler.update(Duration::from_millis(200));
+        }
+        scaler.idle(Duration::from_millis(400));
+        scaler.update(Duration::from_millis(200));
+        scaler.idle(Duration::from_millis(400));
+        assert_eq!(scaler.scale(), scaler.minimum_scale);
+    }
+
+    #[test]
+    fn scaled_size_follows_the_scale() {
+        let mut scaler = scaler();
+        assert_eq!(scaler.scaled_size(800, 600), (800, 600));
+
+        for _ in 0..100 {
+            scaler.update(Duration::from_millis(200));
+        }
+        assert_eq!(scaler.scaled_size(800, 600), (200, 150));
+    }
+
+    #[test]
+    fn scaled_size_keeps_at_least_one_pixel() {
+        let mut scaler = scaler();
+        for _ in 0..100 {
+            scaler.update(Duration::from_millis(200));
+        }
+        assert_eq!(scaler.scaled_size(2, 3), (1, 1));
+        assert_eq!(scaler.scaled_size(0, 0), (1, 1));
+    }
+}
@@ -1,11 +1,15 @@
 extern crate sdl2;
 
-use sdl2::render::WindowCanvas;
+use sdl2::pixels::PixelFormatEnum;
+use sdl2::render::{Texture, WindowCanvas};
 
 
 /// A screen tile that can be used for drawing. (E.g. the content tile of a window or a HTML canvas.)
 pub struct View {
     pub canvas: WindowCanvas,
+
+    /// The texture frames are drawn to with its width and height, kept until the size changes.
+    texture: Option<(Texture, u32, u32)>,
 }
 
 impl View {
@@ -25,6 +29,42 @@ impl View {
             .map_err(|e| e.to_string())
             .unwrap();
 
-        View { canvas }
+        View { canvas, texture: None }
+    }
+
+    /// Draws a frame of the given size into RGB24 pixels and copies it to the canvas, scaled up to the window.
+    ///
+    /// # Parameters:
+    ///     - width:        The width of the frame, in pixels.
+    ///     - height:       The height of the frame, in pixels.
+    ///     - draw:         Draws the frame, given its pixels and the number of bytes per row.
+    pub fn draw_frame<F: FnOnce(&mut [u8], usize)>(&mut self, width: u32, height: u32, draw: F) {
+        let mut texture = match self.texture.take() {
+            Some((texture, w, h)) if (w, h) == (width, height) => texture,
+            old => {
+                if let Some((texture, _, _)) = old {
+                    // SAFETY: The texture is not used after this, and the canvas that created it is still alive
+                    unsafe { texture.destroy() };
+                }
+                match self.canvas.texture_creator().create_texture_streaming(
+                    PixelFormatEnum::RGB24,
+                    width,
+                    height,
+                ) {
+                    Ok(texture) => texture,
+                    Err(e) => {
+                        println!("Error creating texture! {}", e);
+                        panic!("Error creating texture!");
+                    }
+                }
+            }
+        };
+
+        texture.with_lock(None, draw).unwrap();
+        if let Err(e) = self.canvas.copy(&texture, None, None) {
+            println!("Error presenting texture! {}", e);
+            panic!("Error presenting texture!");
+        }
+        self.texture = Some((texture, width, height));
     }
 }
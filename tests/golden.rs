@@ -0,0 +1,323 @@
+//! Renders fixed maps from fixed poses with both renderers and compares the pictures with
+//! reference images in `tests/golden`.
+//!
+//! When a picture differs, it is written to `target/golden` next to an image of the
+//! differences (red where they are). To accept changed pictures as the new references, run
+//! `UPDATE_GOLDEN=1 cargo test --test golden`.
+
+use std::f64::consts::PI;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+
+use hyperbolic_raycaster::game::hypermap::HyperMap;
+use hyperbolic_raycaster::headless::{self, Frame, Pose};
+use hyperbolic_raycaster::layout::Layout;
+use hyperbolic_raycaster::renderer::RendererKind;
+use hyperbolic_raycaster::utils::color::RGBColor;
+
+const WIDTH: usize = 160;
+const HEIGHT: usize = 120;
+
+/// The largest difference of a color channel still counted as the same pixel, for dithering
+/// and rounding that differ between platforms.
+const CHANNEL_TOLERANCE: u8 = 8;
+
+/// The share of pixels allowed to differ by more, for edges landing one pixel further.
+const PIXEL_TOLERANCE: f64 = 0.005;
+
+/// Poses showing walls of `5square.json`: a corner, and a wall seen at an angle.
+const FIVE_SQUARE_POSES: [Pose; 2] = [
+    Pose {
+        forward: 0.5,
+        strafe: 0.2,
+        turn: PI,
+        pitch: 0.,
+    },
+    Pose {
+        forward: 0.3,
+        strafe: 0.,
+        turn: 1.25 * PI,
+        pitch: 0.,
+    },
+];
+
+/// Poses showing the wall of `singlewall.json`: from the start, and from close by.
+const SINGLE_WALL_POSES: [Pose; 2] = [
+    Pose {
+        forward: 0.,
+        strafe: 0.,
+        turn: 0.,
+        pitch: 0.,
+    },
+    Pose {
+        forward: 0.3,
+        strafe: 0.,
+        turn: 1.75 * PI,
+        pitch: 0.,
+    },
+];
+
+/// Poses in the small rooms of `poncairemap2.json`: next to a wall, and looking down at two pillars.
+const POINCARE_MAP_POSES: [Pose; 2] = [
+    Pose {
+        forward: 0.,
+        strafe: 0.,
+        turn: 0.25 * PI,
+        pitch: 0.,
+    },
+    Pose {
+        forward: 0.1,
+        strafe: -0.2,
+        turn: 1.25 * PI,
+        pitch: -0.1,
+    },
+];
+
+/// Renders a picture and compares it with its reference image.
+///
+/// # Parameters:
+///     - name:               Name of the reference image, without the extension.
+///     - map:                The map file's contents.
+///     - pose:               Where the player stands and looks.
+///     - renderer:           The renderer to draw with, in the single layout.
+///     - layout:             How the picture is divided into views.
+///     - debug_ray_step:     Draw every how many'th ray cast on the top-down view, 0 for none.
+fn check(
+    name: &str,
+    map: &str,
+    pose: Pose,
+    renderer: RendererKind,
+    layout: Layout,
+    debug_ray_step: usize,
+) {
+    let actual = headless::render(HyperMap::new(map), &pose, renderer, layout, debug_ray_step, WIDTH, HEIGHT);
+    let reference_path = golden_dir().join(format!("{}.png", name));
+
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        fs::create_dir_all(golden_dir()).unwrap();
+        actual.save_png(&reference_path).unwrap();
+        return;
+    }
+
+    let reference = load_png(&reference_path);
+    assert_eq!(
+        (reference.width(), reference.height()),
+        (actual.width(), actual.height()),
+        "{}: size differs from the reference image",
+        name
+    );
+
+    let mut diff = Frame::new(WIDTH, HEIGHT);
+    let mut canvas = diff.canvas();
+    let mut differing = 0;
+    for y in 0..HEIGHT {
+        for x in 0..WIDTH {
+            let difference = channel_difference(&actual.pixel(x, y), &reference.pixel(x, y));
+            let color = match difference > CHANNEL_TOLERANCE {
+                true => {
+                    differing += 1;
+                    RGBColor {
+                        red: 128 + difference / 2,
+                        green: 0,
+                        blue: 0,
+                    }
+                }
+                false => {
+                    let gray = (luma(&actual.pixel(x, y)) / 4.) as u8;
+                    RGBColor {
+                        red: gray,
+                        green: gray,
+                        blue: gray,
+                    }
+                }
+            };
+            canvas.draw_pixel(x, y, &color);
+        }
+    }
+
+    let share = differing as f64 / (WIDTH * HEIGHT) as f64;
+    if share > PIXEL_TOLERANCE {
+        let output_dir = Path::new(env!("CARGO_MANIFEST_DIR"))
+            .join("target")
+            .join("golden");
+        fs::create_dir_all(&output_dir).unwrap();
+        actual
+            .save_png(output_dir.join(format!("{}.png", name)))
+            .unwrap();
+        diff.save_png(output_dir.join(format!("{}.diff.png", name)))
+            .unwrap();
+        panic!(
+            "{}: {:.2}% of the pixels differ from the reference image, see {}",
+            name,
+            share * 100.,
+            output_dir.display()
+        );
+    }
+}
+
+/// The directory of the reference images.
+fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+}
+
+/// Reads a reference image.
+fn load_png(path: &Path) -> Frame {
+    let file = File::open(path).unwrap_or_else(|e| {
+        panic!(
+            "Cannot open {} ({}), create it with UPDATE_GOLDEN=1",
+            path.display(),
+            e
+        )
+    });
+    let mut reader = png::Decoder::new(file).read_info().unwrap();
+    let mut pixels = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut pixels).unwrap();
+    assert_eq!(
+        info.color_type,
+        png::ColorType::Rgb,
+        "{}: not an RGB image",
+        path.display()
+    );
+
+    let (width, height) = (info.width as usize, info.height as usize);
+    let mut frame = Frame::new(width, height);
+    let mut canvas = frame.canvas();
+    for (index, pixel) in pixels[..width * height * 3].chunks(3).enumerate() {
+        let color = RGBColor {
+            red: pixel[0],
+            green: pixel[1],
+            blue: pixel[2],
+        };
+        canvas.draw_pixel(index % width, index / width, &color);
+    }
+    frame
+}
+
+/// The largest difference between the channels of two colors.
+fn channel_difference(a: &RGBColor, b: &RGBColor) -> u8 {
+    let red = (a.red as i16 - b.red as i16).abs();
+    let green = (a.green as i16 - b.green as i16).abs();
+    let blue = (a.blue as i16 - b.blue as i16).abs();
+    red.max(green).max(blue) as u8
+}
+
+/// Brightness of a color, 0…255.
+fn luma(color: &RGBColor) -> f64 {
+    0.299 * color.red as f64 + 0.587 * color.green as f64 + 0.114 * color.blue as f64
+}
+
+/// Checks the pictures of a map seen from each of the poses, named `<name>_<index of the pose>`.
+fn check_poses(name: &str, map: &str, poses: &[Pose], renderer: RendererKind) {
+    for (index, pose) in poses.iter().enumerate() {
+        check(
+            &format!("{}_{}", name, index),
+            map,
+            *pose,
+            renderer,
+            Layout::Single,
+            0,
+        );
+    }
+}
+
+#[test]
+fn five_square_first_person() {
+    let map = include_str!("../assets/5square.json");
+    check_poses("5square", map, &FIVE_SQUARE_POSES, RendererKind::Hyperbolic);
+}
+
+#[test]
+fn five_square_top_down() {
+    let map = include_str!("../assets/5square.json");
+    check_poses(
+        "5square_top_down",
+        map,
+        &FIVE_SQUARE_POSES,
+        RendererKind::Poincare,
+    );
+}
+
+#[test]
+fn single_wall_first_person() {
+    let map = include_str!("../assets/singlewall.json");
+    check_poses(
+        "singlewall",
+        map,
+        &SINGLE_WALL_POSES,
+        RendererKind::Hyperbolic,
+    );
+}
+
+#[test]
+fn single_wall_top_down() {
+    let map = include_str!("../assets/singlewall.json");
+    check_poses(
+        "singlewall_top_down",
+        map,
+        &SINGLE_WALL_POSES,
+        RendererKind::Poincare,
+    );
+}
+
+#[test]
+fn poincare_map_first_person() {
+    let map = include_str!("../assets/poncairemap2.json");
+    check_poses(
+        "poncairemap2",
+        map,
+        &POINCARE_MAP_POSES,
+        RendererKind::Hyperbolic,
+    );
+}
+
+#[test]
+fn poincare_map_top_down() {
+    let map = include_str!("../assets/poncairemap2.json");
+    check_poses(
+        "poncairemap2_top_down",
+        map,
+        &POINCARE_MAP_POSES,
+        RendererKind::Poincare,
+    );
+}
+
+#[test]
+fn five_square_side_by_side() {
+    let map = include_str!("../assets/5square.json");
+    check(
+        "5square_side_by_side",
+        map,
+        FIVE_SQUARE_POSES[0],
+        RendererKind::Hyperbolic,
+        Layout::SideBySide,
+        0,
+    );
+}
+
+#[test]
+fn five_square_models() {
+    let map = include_str!("../assets/5square.json");
+    check(
+        "5square_models",
+        map,
+        FIVE_SQUARE_POSES[0],
+        RendererKind::Hyperbolic,
+        Layout::Models,
+        0,
+    );
+}
+
+#[test]
+fn five_square_debug_rays() {
+    let map = include_str!("../assets/5square.json");
+    check(
+        "5square_debug_rays",
+        map,
+        FIVE_SQUARE_POSES[1],
+        RendererKind::Hyperbolic,
+        Layout::SideBySide,
+        8,
+    );
+}